@@ -0,0 +1,467 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bitboard::{bit, rank_file, Squares};
+use crate::chessboard::Board;
+use crate::pieces::piece::{Color, PieceKind};
+use crate::pieces::{bishop, king, knight, pawn, rook};
+
+// Static evaluation in centipawns, positive when white is better. Every term
+// has a middlegame and an endgame value, and the two are blended by how
+// much material is left (a "tapered" evaluation), so that e.g. the king
+// hides early on but walks to the centre once the queens are gone.
+//
+// The weights live in EvalWeights, which can be read from a TOML or JSON
+// file to try out a different playing style without rebuilding. The
+// constants below are the defaults.
+
+// Material, indexed by PieceKind::index. The king is never traded, so it counts for nothing.
+const MATERIAL_MG: [i32; 6] = [82, 337, 365, 477, 1025, 0];
+const MATERIAL_EG: [i32; 6] = [94, 281, 297, 512, 936, 0];
+
+// Piece-square tables from white's side, drawn as the board is seen:
+// the first row is the eighth rank
+#[rustfmt::skip]
+const PAWN_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const PAWN_EG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     80,  80,  80,  80,  80,  80,  80,  80,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     30,  30,  30,  30,  30,  30,  30,  30,
+     15,  15,  15,  15,  15,  15,  15,  15,
+      5,   5,   5,   5,   5,   5,   5,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const ROOK_EG: [i32; 64] = [
+      5,   5,   5,   5,   5,   5,   5,   5,
+     10,  10,  10,  10,  10,  10,  10,  10,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const KING_MG: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_EG: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+
+// Per square a knight, bishop, rook or queen can move to
+const MOBILITY_MG: [i32; 6] = [0, 4, 5, 2, 1, 0];
+const MOBILITY_EG: [i32; 6] = [0, 4, 5, 4, 2, 0];
+
+const DOUBLED_PAWN: (i32, i32) = (-10, -20); // per pawn behind another of its color on the file
+const ISOLATED_PAWN: (i32, i32) = (-10, -15); // no friendly pawn on a neighbouring file
+// A passed pawn has no enemy pawn ahead of it on its own or a neighbouring
+// file, and is worth more the further it has come. By rank from its own side.
+const PASSED_PAWN_MG: [i32; 8] = [0, 5, 10, 15, 25, 40, 60, 0];
+const PASSED_PAWN_EG: [i32; 8] = [0, 10, 20, 35, 60, 90, 130, 0];
+
+const BISHOP_PAIR: (i32, i32) = (30, 50);
+
+// King safety only matters while there is enough material to attack with,
+// so these are middlegame terms
+const PAWN_SHIELD: i32 = 10; // per own pawn on the three squares in front of the king
+const KING_ATTACK: [i32; 6] = [0, 2, 2, 3, 5, 0]; // per attacked square next to the enemy king, by attacker
+const KING_ATTACK_SCALE: i32 = 4;
+
+// Game phase from the pieces left: 24 with all of them, 0 with none
+const PHASE: [i32; 6] = [0, 1, 1, 2, 4, 0];
+const MAX_PHASE: i32 = 24;
+
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+
+// One table per piece kind, each an 8x8 board drawn like the tables above
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PieceSquareTables {
+    pub pawn: [[i32; 8]; 8],
+    pub knight: [[i32; 8]; 8],
+    pub bishop: [[i32; 8]; 8],
+    pub rook: [[i32; 8]; 8],
+    pub queen: [[i32; 8]; 8],
+    pub king: [[i32; 8]; 8],
+}
+
+impl PieceSquareTables {
+    fn new(tables: [&[i32; 64]; 6]) -> Self {
+        let rows = |table: &[i32; 64]| std::array::from_fn(|row| std::array::from_fn(|col| table[row * 8 + col]));
+        PieceSquareTables {
+            pawn: rows(tables[0]),
+            knight: rows(tables[1]),
+            bishop: rows(tables[2]),
+            rook: rows(tables[3]),
+            queen: rows(tables[4]),
+            king: rows(tables[5]),
+        }
+    }
+
+    fn table(&self, kind: PieceKind) -> &[[i32; 8]; 8] {
+        match kind {
+            PieceKind::Pawn => &self.pawn,
+            PieceKind::Knight => &self.knight,
+            PieceKind::Bishop => &self.bishop,
+            PieceKind::Rook => &self.rook,
+            PieceKind::Queen => &self.queen,
+            PieceKind::King => &self.king,
+        }
+    }
+}
+
+// Every number the evaluation uses, in centipawns. Arrays by piece kind
+// follow PieceKind::index; pairs are (middlegame, endgame). A weights file
+// may leave out any field to keep its default, but a misspelt one is an error.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EvalWeights {
+    pub material_mg: [i32; 6],
+    pub material_eg: [i32; 6],
+    pub pst_mg: PieceSquareTables,
+    pub pst_eg: PieceSquareTables,
+    pub mobility_mg: [i32; 6],
+    pub mobility_eg: [i32; 6],
+    pub doubled_pawn: (i32, i32),
+    pub isolated_pawn: (i32, i32),
+    pub passed_pawn_mg: [i32; 8],
+    pub passed_pawn_eg: [i32; 8],
+    pub bishop_pair: (i32, i32),
+    pub pawn_shield: i32,
+    pub king_attack: [i32; 6],
+    pub king_attack_scale: i32,
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        EvalWeights {
+            material_mg: MATERIAL_MG,
+            material_eg: MATERIAL_EG,
+            pst_mg: PieceSquareTables::new([&PAWN_MG, &KNIGHT, &BISHOP, &ROOK_MG, &QUEEN, &KING_MG]),
+            pst_eg: PieceSquareTables::new([&PAWN_EG, &KNIGHT, &BISHOP, &ROOK_EG, &QUEEN, &KING_EG]),
+            mobility_mg: MOBILITY_MG,
+            mobility_eg: MOBILITY_EG,
+            doubled_pawn: DOUBLED_PAWN,
+            isolated_pawn: ISOLATED_PAWN,
+            passed_pawn_mg: PASSED_PAWN_MG,
+            passed_pawn_eg: PASSED_PAWN_EG,
+            bishop_pair: BISHOP_PAIR,
+            pawn_shield: PAWN_SHIELD,
+            king_attack: KING_ATTACK,
+            king_attack_scale: KING_ATTACK_SCALE,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WeightsError {
+    Io(std::io::Error),
+    Toml(String),
+    Json(String),
+}
+
+impl std::fmt::Display for WeightsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WeightsError::Io(e) => write!(f, "{}", e),
+            WeightsError::Toml(msg) => write!(f, "invalid TOML weights: {}", msg),
+            WeightsError::Json(msg) => write!(f, "invalid JSON weights: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for WeightsError {}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+impl EvalWeights {
+    // Reads a .json file as JSON and anything else as TOML
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, WeightsError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(WeightsError::Io)?;
+        if is_json(path) {
+            serde_json::from_str(&text).map_err(|e| WeightsError::Json(e.to_string()))
+        } else {
+            toml::from_str(&text).map_err(|e| {
+                let line = e.span().map_or(1, |span| text[..span.start].matches('\n').count() + 1);
+                WeightsError::Toml(format!("line {}: {}", line, e.message()))
+            })
+        }
+    }
+
+    // Writes every weight, as JSON for a .json file and TOML otherwise
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), WeightsError> {
+        let path = path.as_ref();
+        let text = if is_json(path) {
+            serde_json::to_string_pretty(self).map_err(|e| WeightsError::Json(e.to_string()))?
+        } else {
+            toml::to_string(self).map_err(|e| WeightsError::Toml(e.to_string()))?
+        };
+        std::fs::write(path, text).map_err(WeightsError::Io)
+    }
+
+    // The middlegame value of a piece, for the search to judge captures by
+    pub fn piece_value(&self, kind: PieceKind) -> i32 {
+        self.material_mg[kind.index()]
+    }
+}
+
+pub fn evaluate(board: &Board, weights: &EvalWeights) -> i32 {
+    let (white_mg, white_eg) = side_terms(board, Color::White, weights);
+    let (black_mg, black_eg) = side_terms(board, Color::Black, weights);
+    let phase = PieceKind::ALL
+        .iter()
+        .map(|&kind| PHASE[kind.index()] * (board.pieces(Color::White, kind) | board.pieces(Color::Black, kind)).count_ones() as i32)
+        .sum::<i32>()
+        .min(MAX_PHASE);
+    let mg = white_mg - black_mg;
+    let eg = white_eg - black_eg;
+    (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+// Middlegame and endgame score of one side's pieces
+fn side_terms(board: &Board, color: Color, weights: &EvalWeights) -> (i32, i32) {
+    let mut mg = 0;
+    let mut eg = 0;
+    let occupied = board.occupied();
+    let own = board.occupancy(color);
+    let enemy_king = board.pieces(color.opponent(), PieceKind::King).trailing_zeros() as usize;
+    let king_zone = king::ATTACKS[enemy_king] | bit(enemy_king);
+    let mut king_attack = 0;
+
+    for kind in PieceKind::ALL {
+        for sq in Squares(board.pieces(color, kind)) {
+            let i = kind.index();
+            // The tables are drawn with the eighth rank first, which is black's
+            // view of its own squares and a vertical flip of white's
+            let (row, col) = if color == Color::White { (7 - sq / 8, sq % 8) } else { (sq / 8, sq % 8) };
+            mg += weights.material_mg[i] + weights.pst_mg.table(kind)[row][col];
+            eg += weights.material_eg[i] + weights.pst_eg.table(kind)[row][col];
+
+            let attacks = match kind {
+                PieceKind::Knight => knight::ATTACKS[sq],
+                PieceKind::Bishop => bishop::attacks(sq, occupied),
+                PieceKind::Rook => rook::attacks(sq, occupied),
+                PieceKind::Queen => bishop::attacks(sq, occupied) | rook::attacks(sq, occupied),
+                _ => 0,
+            };
+            let moves = (attacks & !own).count_ones() as i32;
+            mg += weights.mobility_mg[i] * moves;
+            eg += weights.mobility_eg[i] * moves;
+            king_attack += weights.king_attack[i] * (attacks & king_zone).count_ones() as i32;
+        }
+    }
+    mg += weights.king_attack_scale * king_attack;
+
+    let (pawn_mg, pawn_eg) = pawn_structure(board, color, weights);
+    mg += pawn_mg;
+    eg += pawn_eg;
+
+    if board.pieces(color, PieceKind::Bishop).count_ones() >= 2 {
+        mg += weights.bishop_pair.0;
+        eg += weights.bishop_pair.1;
+    }
+
+    // Pawns on the three squares in front of the king, one rank up
+    let king_sq = board.pieces(color, PieceKind::King).trailing_zeros() as usize;
+    let shield = pawn::ATTACKS[color.index()][king_sq] | forward(bit(king_sq), color);
+    mg += weights.pawn_shield * (shield & board.pieces(color, PieceKind::Pawn)).count_ones() as i32;
+    (mg, eg)
+}
+
+fn pawn_structure(board: &Board, color: Color, weights: &EvalWeights) -> (i32, i32) {
+    let pawns = board.pieces(color, PieceKind::Pawn);
+    let enemy_pawns = board.pieces(color.opponent(), PieceKind::Pawn);
+    let mut mg = 0;
+    let mut eg = 0;
+    for file in 0..8 {
+        let count = (pawns & (FILE_A << file)).count_ones() as i32;
+        if count > 1 {
+            mg += weights.doubled_pawn.0 * (count - 1);
+            eg += weights.doubled_pawn.1 * (count - 1);
+        }
+    }
+    for sq in Squares(pawns) {
+        let (rank, file) = rank_file(sq);
+        let neighbours = adjacent_files(file);
+        if pawns & neighbours == 0 {
+            mg += weights.isolated_pawn.0;
+            eg += weights.isolated_pawn.1;
+        }
+        if enemy_pawns & ahead(sq, color) & (neighbours | FILE_A << file) == 0 {
+            let relative_rank = if color == Color::White { rank } else { 7 - rank } as usize;
+            mg += weights.passed_pawn_mg[relative_rank];
+            eg += weights.passed_pawn_eg[relative_rank];
+        }
+    }
+    (mg, eg)
+}
+
+fn adjacent_files(file: i32) -> u64 {
+    let left = if file > 0 { FILE_A << (file - 1) } else { 0 };
+    let right = if file < 7 { FILE_A << (file + 1) } else { 0 };
+    left | right
+}
+
+// Every square on a rank beyond `sq`'s, seen from `color`
+fn ahead(sq: usize, color: Color) -> u64 {
+    let rank = sq / 8;
+    match color {
+        Color::White if rank < 7 => !0u64 << ((rank + 1) * 8),
+        Color::White => 0,
+        Color::Black => (1u64 << (rank * 8)) - 1,
+    }
+}
+
+// The squares one rank further up the board for `color`
+fn forward(squares: u64, color: Color) -> u64 {
+    match color {
+        Color::White => squares << 8,
+        Color::Black => squares >> 8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The same position with the colors swapped and the board turned over
+    fn mirrored(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let swap_case = |s: &str| -> String {
+            s.chars().map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect()
+        };
+        let placement: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        let en_passant = fields[3].replace('3', "x").replace('6', "3").replace('x', "6");
+        format!("{} {} {} {} {} {}", placement.join("/"), side, swap_case(fields[2]), en_passant, fields[4], fields[5])
+    }
+
+    #[test]
+    fn mirrored_positions_score_opposite() {
+        let weights = EvalWeights::default();
+        assert_eq!(evaluate(&Board::new(), &weights), 0);
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let flipped = Board::from_fen(&mirrored(fen)).unwrap();
+            assert_eq!(evaluate(&board, &weights), -evaluate(&flipped, &weights), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn rewards_structure_and_development() {
+        let weights = EvalWeights::default();
+        let eval = |fen: &str| evaluate(&Board::from_fen(fen).unwrap(), &weights);
+        // A passed pawn on the sixth beats one blocked by an enemy pawn
+        assert!(eval("4k3/8/3P4/8/8/8/8/4K3 w - - 0 1") > eval("4k3/3p4/3P4/8/8/8/8/4K3 w - - 0 1") + 90);
+        // Doubled pawns are worse than connected ones
+        assert!(eval("4k3/8/8/8/8/3P4/3P4/4K3 w - - 0 1") < eval("4k3/8/8/8/8/8/3PP3/4K3 w - - 0 1"));
+        // A developed knight beats one on its home square
+        assert!(eval("4k3/8/8/8/8/5N2/8/4K3 w - - 0 1") > eval("4k3/8/8/8/8/8/8/4K1N1 w - - 0 1"));
+        // Two bishops beat bishop and knight
+        assert!(eval("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1") > eval("4k3/8/8/8/8/8/8/2B1KN2 w - - 0 1"));
+    }
+
+    #[test]
+    fn weights_files_round_trip() {
+        let weights = EvalWeights::default();
+        let text = toml::to_string(&weights).unwrap();
+        assert_eq!(toml::from_str::<EvalWeights>(&text).unwrap(), weights);
+        let json = serde_json::to_string(&weights).unwrap();
+        assert_eq!(serde_json::from_str::<EvalWeights>(&json).unwrap(), weights);
+        // Fields left out keep their defaults
+        let aggressive: EvalWeights = toml::from_str("king_attack_scale = 12\nbishop_pair = [50, 60]").unwrap();
+        assert_eq!(aggressive.king_attack_scale, 12);
+        assert_eq!(aggressive.bishop_pair, (50, 60));
+        assert_eq!(aggressive.material_mg, weights.material_mg);
+    }
+}
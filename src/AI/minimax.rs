@@ -1,108 +1,637 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-struct PieceValues {
-    pawn: i32,
-    knight: i32,
-    bishop: i32,
-    rook: i32,
-    queen: i32,
-    king: i32,
-}
-
-impl PieceValues {
-    fn new() -> Self {
-        PieceValues {
-            pawn: 1,
-            knight: 3,
-            bishop: 3,
-            rook: 5,
-            queen: 9,
-            king: 1000, // Arbitrary high value to prioritize king safety
-        }
-    }
-}
-
-fn evaluate_board(board: &crate::chessboard::Board) -> i32 {
-    let values = PieceValues::new();
-    let mut score = 0;
-
-    for p in &board.pieces {
-        let piece_value = match p.get_name() {
-            "P" => values.pawn,
-            "N" => values.knight,
-            "B" => values.bishop,
-            "R" => values.rook,
-            "Q" => values.queen,
-            "K" => values.king,
-            _ => 0,
-        };
+use crate::bitboard::square;
+use crate::chessboard::{Board, Move};
+use crate::pieces::piece::Color;
+use super::eval::{evaluate, EvalWeights};
+use super::tt::{Bound, Entry, PackedMove, TranspositionTable};
 
-        if p.get_color() == "white" {
-            score += piece_value;
-        } else {
-            score -= piece_value;
-        }
-    }        
-    score
+// A capture is skipped in quiescence when even winning the piece and this
+// much more would leave the side to move below alpha
+const DELTA_MARGIN: i32 = 200;
+
+// The deepest iteration a search starts when nothing else limits it
+pub const MAX_DEPTH: i32 = 64;
+
+// Bounds wider than any evaluation
+const INFINITY: i32 = i32::MAX / 4;
+
+// Being checkmated right now scores -MATE. A mate found `ply` half-moves
+// into the search scores MATE - ply for the winner, so nearer mates beat
+// farther ones and the losing side prefers the longest defence.
+pub const MATE: i32 = 100_000;
+
+// Check extensions can take a line past MAX_DEPTH, but never past this
+const MAX_PLY: i32 = 128;
+
+// Full moves to mate for a mate score: positive when the side to move
+// mates, negative when it gets mated. None for ordinary scores.
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score >= MATE - MAX_PLY {
+        Some((MATE - score + 1) / 2)
+    } else if score <= -MATE + MAX_PLY {
+        Some(-(MATE + score + 1) / 2)
+    } else {
+        None
+    }
 }
 
-// Alpha-beta minimax. We provide a wrapper `minimax` that calls `minimax_ab` with
-// initial alpha/beta values. Moves generated are filtered to legal moves by
-// simulating and skipping any that leave the mover's king in check.
+// The table is shared by every path to a position, so mate scores are
+// stored as distances from the position itself rather than from the root
+fn score_to_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE - MAX_PLY {
+        score + ply
+    } else if score <= -MATE + MAX_PLY {
+        score - ply
+    } else {
+        score
+    }
+}
 
-fn minimax_ab(board: &crate::chessboard::Board, depth: i32, mut alpha: i32, mut beta: i32, is_maximizing: bool) -> i32 {
-    if depth == 0 || board.is_game_over() {
-        return evaluate_board(board);
+fn score_from_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE - MAX_PLY {
+        score - ply
+    } else if score <= -MATE + MAX_PLY {
+        score + ply
+    } else {
+        score
     }
+}
+
+// When to give up deepening. The search always finishes depth 1 unless
+// stopped, and an iteration cut short by a limit is thrown away.
+#[derive(Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<i32>,
+    pub time: Option<Duration>,
+    pub nodes: Option<u64>,
+    pub stop: Option<Arc<AtomicBool>>, // set from another thread to end the search early
+}
+
+impl SearchLimits {
+    pub fn depth(depth: i32) -> Self {
+        SearchLimits { depth: Some(depth), ..Default::default() }
+    }
+
+    pub fn time(time: Duration) -> Self {
+        SearchLimits { time: Some(time), ..Default::default() }
+    }
+}
+
+// Counters for judging move ordering: the better it is, the more cutoffs
+// come from the first move tried and the fewer nodes a search needs
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SearchStats {
+    pub tt_hits: u64, // nodes settled by the transposition table
+    pub cutoffs: u64,
+    pub first_move_cutoffs: u64,
+}
+
+// The outcome of the deepest completed iteration. Scores are in centipawns from
+// the point of view of the side to move, except mate scores (see MATE).
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub pv: Vec<Move>, // best line for both sides, starting with best_move
+    pub nodes: u64,
+    pub depth: i32,
+    pub time: Duration,
+    pub stats: SearchStats,
+}
+
+// What the threads of one search share
+struct Shared<'a> {
+    limits: &'a SearchLimits,
+    tt: &'a TranspositionTable,
+    weights: &'a EvalWeights,
+    start: Instant,
+    nodes: AtomicU64, // every thread's nodes, added in batches
+    done: AtomicBool, // set when the main thread has finished, to stop the helpers
+}
+
+impl<'a> Shared<'a> {
+    fn new(limits: &'a SearchLimits, tt: &'a TranspositionTable, weights: &'a EvalWeights) -> Self {
+        Shared { limits, tt, weights, start: Instant::now(), nodes: AtomicU64::new(0), done: AtomicBool::new(false) }
+    }
+}
+
+// One thread's search. Killers and history are its own.
+struct Searcher<'a> {
+    shared: &'a Shared<'a>,
+    nodes: u64,
+    flushed: u64, // how many of `nodes` have been added to the shared count
+    stopped: bool,
+    stats: SearchStats,
+    killers: [[Option<Move>; 2]; MAX_PLY as usize], // quiet moves that caused a cutoff, by ply
+    history: [[[i32; 64]; 64]; 2], // [color][from][to], how often a quiet move caused a cutoff
+}
 
-    if is_maximizing {
-        let mut value = i32::MIN;
-        let color = "white";
-        let moves = board.get_all_moves(color);
-        // If no generated moves, return evaluation
-        if moves.is_empty() {
-            return evaluate_board(board);
+impl<'a> Searcher<'a> {
+    fn new(shared: &'a Shared<'a>) -> Self {
+        Searcher {
+            shared,
+            nodes: 0,
+            flushed: 0,
+            stopped: false,
+            stats: SearchStats::default(),
+            killers: [[None; 2]; MAX_PLY as usize],
+            history: [[[0; 64]; 64]; 2],
         }
+    }
+
+    fn flush_nodes(&mut self) {
+        self.shared.nodes.fetch_add(self.nodes - self.flushed, Ordering::Relaxed);
+        self.flushed = self.nodes;
+    }
+
+    // Nodes searched by all threads, give or take the batches not yet added
+    fn total_nodes(&self) -> u64 {
+        self.shared.nodes.load(Ordering::Relaxed) + self.nodes - self.flushed
+    }
+
+    fn limit_reached(&self) -> bool {
+        let limits = self.shared.limits;
+        self.shared.done.load(Ordering::Relaxed)
+            || limits.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed))
+            || limits.time.is_some_and(|time| self.shared.start.elapsed() >= time)
+            || limits.nodes.is_some_and(|nodes| self.total_nodes() >= nodes)
+    }
+
+    // Inside the tree limits are looked at every 1024 nodes so the clock isn't read constantly
+    fn out_of_budget(&mut self) -> bool {
+        if !self.stopped && (self.nodes.is_multiple_of(1024) || self.shared.limits.nodes.is_some()) {
+            self.flush_nodes();
+            self.stopped = self.limit_reached();
+        }
+        self.stopped
+    }
+
+    // Negamax alpha-beta: scores are from the side to move's point of view, so
+    // one branch serves both colors. Moves are generated pseudo-legally and
+    // skipped when they leave the mover in check; every move is taken back
+    // with unmake_move, so the board is never cloned. `pv` receives the best
+    // line found below this node, `ply` is the distance from the root.
+    fn negamax(&mut self, board: &mut Board, depth: i32, ply: i32, mut alpha: i32, beta: i32, pv: &mut Vec<Move>) -> i32 {
+        let color = board.get_current_turn();
+        // A side in check at the horizon gets one more ply, so a mate there
+        // is seen as a mate instead of a material count
+        let in_check = board.is_in_check(color);
+        if depth <= 0 && !in_check {
+            return self.quiescence(board, ply, alpha, beta, pv);
+        }
+        self.nodes += 1;
+        if ply >= MAX_PLY {
+            return side_relative(color, evaluate(board, self.shared.weights));
+        }
+        if self.out_of_budget() {
+            return 0;
+        }
+        // A position seen before in the game or the line searched is as good
+        // as a draw, as is one the fifty-move rule ends
+        if board.repetition_count() >= 2 || board.halfmove_clock >= 100 {
+            return 0;
+        }
+
+        // A result stored from at least this deep settles the node if its
+        // bound fits the window; otherwise its best move is tried first
+        let hash = board.hash();
+        let entry = self.shared.tt.probe(hash);
+        if let Some(entry) = entry.filter(|entry| entry.depth >= depth) {
+            let score = score_from_tt(entry.score, ply);
+            let cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if cutoff {
+                self.stats.tt_hits += 1;
+                pv.clear();
+                if let Some(packed) = entry.best_move.filter(|_| entry.bound == Bound::Exact) {
+                    pv.extend(board.get_all_moves(color).into_iter().find(|m| packed.matches(m)));
+                }
+                return score;
+            }
+        }
+        let mut moves = board.get_all_moves(color);
+        let tt_move = entry.and_then(|entry| entry.best_move);
+        moves.sort_by_cached_key(|m| -self.order_key(m, tt_move, color, ply));
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        let mut legal_moves = 0;
+        let mut child_pv = Vec::new();
         for m in moves {
-            // simulate
-            let mut new_board = board.clone();
-            new_board.make_move(m);
-            // skip illegal moves that leave own king in check
-            if new_board.is_in_check(color) {
+            if board.castles_through_check(&m) {
+                continue;
+            }
+            let undo = board.apply_move(m);
+            if board.is_in_check(color) {
+                board.unmake_move(&m, &undo);
                 continue;
             }
-            let score = minimax_ab(&new_board, depth - 1, alpha, beta, false);
-            value = value.max(score);
-            alpha = alpha.max(value);
+            legal_moves += 1;
+            child_pv.clear();
+            let score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha, &mut child_pv);
+            board.unmake_move(&m, &undo);
+            if self.stopped {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                best_move = Some(m);
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(m);
+                    pv.extend_from_slice(&child_pv);
+                }
+            }
             if alpha >= beta {
-                break; // beta cutoff
+                self.record_cutoff(m, color, depth, ply, legal_moves == 1);
+                break;
             }
         }
-        value
-    } else {
-        let mut value = i32::MAX;
-        let color = "black";
-        let moves = board.get_all_moves(color);
-        if moves.is_empty() {
-            return evaluate_board(board);
+        // No legal move: checkmate, or stalemate which is a draw
+        if legal_moves == 0 {
+            return if in_check { -(MATE - ply) } else { 0 };
         }
+
+        let bound = if best >= beta {
+            Bound::Lower
+        } else if best > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        let best_move = best_move.map(|m| PackedMove::new(&m));
+        self.shared.tt.store(Entry { hash, depth, score: score_to_tt(best, ply), bound, best_move });
+        best
+    }
+
+    // Past the horizon only captures and promotions are searched, until the
+    // position is quiet, so the search never stops halfway through an
+    // exchange. The side to move may also "stand pat" on the static
+    // evaluation instead of capturing, since it is never forced to capture.
+    // `pv` receives the captures that lead to the quiet position the score
+    // comes from.
+    fn quiescence(&mut self, board: &mut Board, ply: i32, mut alpha: i32, beta: i32, pv: &mut Vec<Move>) -> i32 {
+        let color = board.get_current_turn();
+        self.nodes += 1;
+        let stand_pat = side_relative(color, evaluate(board, self.shared.weights));
+        if ply >= MAX_PLY || self.out_of_budget() || stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut moves: Vec<Move> = board
+            .get_all_moves(color)
+            .into_iter()
+            .filter(|m| m.is_capture() || m.placed_piece() != m.piece)
+            .collect();
+        moves.sort_by_cached_key(|m| -self.order_key(m, None, color, ply));
+
+        let mut best = stand_pat;
+        let mut child_pv = Vec::new();
         for m in moves {
-            let mut new_board = board.clone();
-            new_board.make_move(m);
-            if new_board.is_in_check(color) {
+            // Delta pruning: hopeless captures aren't worth searching
+            let promotion_gain = self.shared.weights.piece_value(m.placed_piece()) - self.shared.weights.piece_value(m.piece);
+            let gain = m.captured.map_or(0, |victim| self.shared.weights.piece_value(victim)) + promotion_gain;
+            if stand_pat + gain + DELTA_MARGIN <= alpha {
                 continue;
             }
-            let score = minimax_ab(&new_board, depth - 1, alpha, beta, true);
-            value = value.min(score);
-            beta = beta.min(value);
+            let undo = board.apply_move(m);
+            if board.is_in_check(color) {
+                board.unmake_move(&m, &undo);
+                continue;
+            }
+            child_pv.clear();
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha, &mut child_pv);
+            board.unmake_move(&m, &undo);
+            if self.stopped {
+                return 0;
+            }
+            if score > best {
+                best = score;
+                if score > alpha {
+                    alpha = score;
+                    pv.clear();
+                    pv.push(m);
+                    pv.extend_from_slice(&child_pv);
+                }
+            }
             if alpha >= beta {
-                break; // alpha cutoff
+                break;
+            }
+        }
+        best
+    }
+
+    // Higher goes first: the table's move, then captures and promotions with
+    // the most valuable victim and least valuable attacker first (MVV-LVA),
+    // then the killers of this ply, then other quiet moves by history
+    fn order_key(&self, m: &Move, tt_move: Option<PackedMove>, color: Color, ply: i32) -> i32 {
+        if tt_move.is_some_and(|packed| packed.matches(m)) {
+            return 3_000_000;
+        }
+        let promoted = m.placed_piece() != m.piece;
+        let gain = m.captured.map_or(0, |victim| 10 * (victim.index() as i32 + 1))
+            + if promoted { 10 * m.placed_piece().index() as i32 } else { 0 };
+        if gain > 0 {
+            return 2_000_000 + gain - m.piece.index() as i32;
+        }
+        let killers = &self.killers[ply as usize];
+        if killers[0] == Some(*m) {
+            1_900_000
+        } else if killers[1] == Some(*m) {
+            1_800_000
+        } else {
+            let (from, to) = (square(m.from.0, m.from.1), square(m.to.0, m.to.1));
+            self.history[color.index()][from][to]
+        }
+    }
+
+    // A quiet move that refutes one line often refutes its siblings too
+    fn record_cutoff(&mut self, m: Move, color: Color, depth: i32, ply: i32, first: bool) {
+        self.stats.cutoffs += 1;
+        if first {
+            self.stats.first_move_cutoffs += 1;
+        }
+        if m.is_capture() || m.placed_piece() != m.piece {
+            return;
+        }
+        let killers = &mut self.killers[ply as usize];
+        if killers[0] != Some(m) {
+            killers[1] = killers[0];
+            killers[0] = Some(m);
+        }
+        let (from, to) = (square(m.from.0, m.from.1), square(m.to.0, m.to.1));
+        let history = &mut self.history[color.index()];
+        history[from][to] += depth * depth;
+        // Keep history scores below the killers by halving them all now and then
+        if history[from][to] > 1_000_000 {
+            history.iter_mut().flatten().for_each(|score| *score /= 2);
+        }
+    }
+}
+
+fn side_relative(color: Color, white_score: i32) -> i32 {
+    match color {
+        Color::White => white_score,
+        Color::Black => -white_score,
+    }
+}
+
+// Searches one ply deeper each iteration until a limit is hit, calling
+// `on_iteration` after every completed depth. The previous iteration's best
+// line is searched first, which makes the next one cheaper. `tt` can be kept
+// between searches, so what was learned on one move helps with the next.
+// Positions are judged by `weights`; a table filled with other weights
+// should be cleared first.
+//
+// With more than one thread this is a "Lazy SMP" search: helper threads
+// search the same position at the same time, sharing nothing but the
+// table. What they find reaches the main thread as table entries that
+// settle or order its nodes, and the main thread's result is returned.
+pub fn iterative_deepening(
+    board: &Board,
+    limits: &SearchLimits,
+    tt: &TranspositionTable,
+    weights: &EvalWeights,
+    threads: usize,
+    mut on_iteration: impl FnMut(&SearchResult),
+) -> SearchResult {
+    let shared = Shared::new(limits, tt, weights);
+    let mut searcher = Searcher::new(&shared);
+    let mut moves = board.get_all_legal_moves();
+    let color = board.get_current_turn();
+    moves.sort_by_cached_key(|m| -searcher.order_key(m, None, color, 0));
+    let mut result = SearchResult {
+        best_move: moves.first().copied(),
+        score: 0,
+        pv: Vec::new(),
+        nodes: 0,
+        depth: 0,
+        time: Duration::ZERO,
+        stats: SearchStats::default(),
+    };
+    if moves.is_empty() {
+        return result;
+    }
+
+    std::thread::scope(|scope| {
+        for id in 1..threads {
+            let (shared, root, moves) = (&shared, board.clone(), moves.clone());
+            scope.spawn(move || help(shared, root, moves, id));
+        }
+
+        let mut root = board.clone();
+        for depth in 1..=limits.depth.unwrap_or(MAX_DEPTH) {
+            let Some((score, pv)) = searcher.search_root(&mut root, &mut moves, depth) else { break };
+            result = SearchResult {
+                best_move: pv.first().copied(),
+                score,
+                pv,
+                nodes: searcher.total_nodes(),
+                depth,
+                time: shared.start.elapsed(),
+                stats: searcher.stats,
+            };
+            on_iteration(&result);
+            // With a single reply there is nothing to choose between, and a mate
+            // within the searched depth can't get any shorter. The next
+            // iteration takes several times as long, so don't start one that
+            // can't finish.
+            let mate_found = score.abs() >= MATE - depth;
+            let half_time_used = limits.time.is_some_and(|time| shared.start.elapsed() * 2 >= time);
+            if moves.len() == 1 || mate_found || half_time_used || searcher.limit_reached() {
+                break;
+            }
+        }
+        searcher.flush_nodes();
+        shared.done.store(true, Ordering::Relaxed);
+    });
+    // Nodes and time cover the whole search, including a cut-short
+    // iteration and the helpers
+    result.nodes = shared.nodes.load(Ordering::Relaxed);
+    result.time = shared.start.elapsed();
+    result.stats = searcher.stats;
+    result
+}
+
+impl Searcher<'_> {
+    // One iteration over the root moves, ending with the best of them moved
+    // to the front. Returns its score and line, or None if stopped midway.
+    fn search_root(&mut self, root: &mut Board, moves: &mut [Move], depth: i32) -> Option<(i32, Vec<Move>)> {
+        let mut alpha = -INFINITY;
+        let mut best = 0;
+        let mut pv = Vec::new();
+        let mut child_pv = Vec::new();
+        for (i, &m) in moves.iter().enumerate() {
+            let undo = root.apply_move(m);
+            child_pv.clear();
+            let score = -self.negamax(root, depth - 1, 1, -INFINITY, -alpha, &mut child_pv);
+            root.unmake_move(&m, &undo);
+            if self.stopped {
+                return None;
             }
+            if score > alpha || pv.is_empty() {
+                alpha = score;
+                best = i;
+                pv.clear();
+                pv.push(m);
+                pv.extend_from_slice(&child_pv);
+            }
+        }
+        moves[..=best].rotate_right(1);
+        Some((alpha, pv))
+    }
+}
+
+// A helper thread's share of a Lazy SMP search. Every other helper starts a
+// ply deeper, so the threads don't all walk the same tree in step.
+fn help(shared: &Shared, mut root: Board, mut moves: Vec<Move>, id: usize) {
+    let mut searcher = Searcher::new(shared);
+    let first_depth = 1 + (id % 2) as i32;
+    for depth in first_depth..=shared.limits.depth.unwrap_or(MAX_DEPTH) {
+        if searcher.search_root(&mut root, &mut moves, depth).is_none() {
+            break;
         }
-        value
     }
+    searcher.flush_nodes();
+}
+
+// The quiescence score of a position for the side to move, and the
+// captures leading to the quiet position it was taken from
+pub fn quiescence(board: &Board, weights: &EvalWeights) -> (i32, Vec<Move>) {
+    let limits = SearchLimits::default();
+    let tt = TranspositionTable::new(0);
+    let shared = Shared::new(&limits, &tt, weights);
+    let mut pv = Vec::new();
+    let score = Searcher::new(&shared).quiescence(&mut board.clone(), 0, -INFINITY, INFINITY, &mut pv);
+    (score, pv)
+}
+
+// Finds the best move for the side to move within `limits`, on one thread
+// with a transposition table of its own
+pub fn search(board: &Board, limits: &SearchLimits, weights: &EvalWeights) -> SearchResult {
+    iterative_deepening(board, limits, &TranspositionTable::default(), weights, 1, |_| {})
 }
 
-pub fn minimax(board: &mut crate::chessboard::Board, depth: i32, is_maximizing: bool) -> i32 {
-    // Use reasonably wide initial alpha/beta
-    minimax_ab(board, depth, i32::MIN / 4, i32::MAX / 4, is_maximizing)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn best(fen: &str, depth: i32) -> (String, i32) {
+        let result = search(&Board::from_fen(fen).unwrap(), &SearchLimits::depth(depth), &EvalWeights::default());
+        (result.best_move.unwrap().to_uci(), result.score)
+    }
+
+    // Score of a fixed-depth search, even when the side to move has a single reply
+    fn score(fen: &str, depth: i32) -> i32 {
+        let limits = SearchLimits::depth(depth);
+        let tt = TranspositionTable::new(1);
+        let weights = EvalWeights::default();
+        let shared = Shared::new(&limits, &tt, &weights);
+        let mut searcher = Searcher::new(&shared);
+        searcher.negamax(&mut Board::from_fen(fen).unwrap(), depth, 0, -INFINITY, INFINITY, &mut Vec::new())
+    }
+
+    #[test]
+    fn finds_the_quickest_mate() {
+        let (m, score) = best("6k1/5ppp/8/8/8/8/q4PPP/3R2K1 w - - 0 1", 4);
+        assert_eq!(m, "d1d8");
+        assert_eq!(mate_in(score), Some(1));
+        // Rh7, then Rg8 mates
+        let (_, score) = best("k7/8/8/8/8/8/6R1/4K2R w - - 0 1", 4);
+        assert_eq!(mate_in(score), Some(2));
+    }
+
+    #[test]
+    fn sees_being_mated() {
+        // Ka7 is forced and Ra1 mates
+        assert_eq!(score("k7/8/8/8/8/8/1R6/1R4K1 b - - 0 1", 3), -(MATE - 2));
+        assert_eq!(mate_in(-(MATE - 2)), Some(-1));
+        assert_eq!(score("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1", 1), -MATE);
+    }
+
+    #[test]
+    fn orders_captures_by_victim_then_attacker() {
+        // The queen on d4 can be taken by the c3 pawn or the f3 knight; the rook on a7 by the a1 rook
+        let board = Board::from_fen("4k3/r7/8/8/3q4/2P2N2/8/R3K3 w - - 0 1").unwrap();
+        let limits = SearchLimits::depth(1);
+        let tt = TranspositionTable::new(1);
+        let weights = EvalWeights::default();
+        let shared = Shared::new(&limits, &tt, &weights);
+        let searcher = Searcher::new(&shared);
+        let mut moves = board.get_all_legal_moves();
+        moves.sort_by_cached_key(|m| -searcher.order_key(m, None, Color::White, 0));
+        let first: Vec<String> = moves[..3].iter().map(|m| m.to_uci()).collect();
+        assert_eq!(first, ["c3d4", "f3d4", "a1a7"]);
+    }
+
+    #[test]
+    fn sees_the_recapture_past_the_horizon() {
+        // Qxd5 wins a pawn at depth 1, but exd5 takes the queen back
+        assert_ne!(best("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", 1).0, "d1d5");
+        // Taking the undefended knight is fine
+        assert_eq!(best("4k3/8/8/3n4/8/8/8/3QK3 w - - 0 1", 1).0, "d1d5");
+    }
+
+    #[test]
+    fn stalemate_is_a_draw() {
+        assert_eq!(score("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1", 2), 0);
+        // Qc8 mates, where Qc7 would only stalemate
+        assert_eq!(best("k7/8/1K6/8/8/8/8/2Q5 w - - 0 1", 2).0, "c1c8");
+    }
+
+    #[test]
+    fn stops_when_asked() {
+        let board = Board::new();
+        let stop = Arc::new(AtomicBool::new(true));
+        let limits = SearchLimits { stop: Some(stop), ..Default::default() };
+        let result = search(&board, &limits, &EvalWeights::default());
+        assert!(result.depth <= 1);
+        assert!(board.get_all_legal_moves().contains(&result.best_move.unwrap()));
+    }
+
+    #[test]
+    fn keeps_to_the_node_limit() {
+        let limits = SearchLimits { nodes: Some(20_000), ..Default::default() };
+        let result = search(&Board::new(), &limits, &EvalWeights::default());
+        assert!((20_000..20_100).contains(&result.nodes), "{} nodes", result.nodes);
+        // The cut-short iteration is thrown away, but the ones before it count
+        assert!(result.depth >= 1 && result.best_move.is_some());
+    }
+
+    #[test]
+    fn keeps_to_the_time_limit() {
+        let limits = SearchLimits::time(Duration::from_millis(100));
+        let start = Instant::now();
+        let result = search(&Board::new(), &limits, &EvalWeights::default());
+        assert!(start.elapsed() < Duration::from_millis(300), "took {:?}", start.elapsed());
+        assert!(result.depth >= 1 && result.best_move.is_some());
+    }
+
+    #[test]
+    fn searches_on_several_threads() {
+        let tt = TranspositionTable::new(1);
+        let weights = EvalWeights::default();
+        // Rh7, then Rg8 mates. The main thread stops at the mate, and the
+        // helpers, with no limit of their own, have to stop with it.
+        let board = Board::from_fen("k7/8/8/8/8/8/6R1/4K2R w - - 0 1").unwrap();
+        let result = iterative_deepening(&board, &SearchLimits::default(), &tt, &weights, 4, |_| {});
+        assert!(board.get_all_legal_moves().contains(&result.best_move.unwrap()));
+        assert_eq!(mate_in(result.score), Some(2));
+
+        // The node limit counts every thread's nodes
+        tt.clear();
+        let limits = SearchLimits { nodes: Some(20_000), ..Default::default() };
+        let result = iterative_deepening(&Board::new(), &limits, &tt, &weights, 4, |_| {});
+        assert!((20_000..21_000).contains(&result.nodes), "{} nodes", result.nodes);
+        assert!(Board::new().get_all_legal_moves().contains(&result.best_move.unwrap()));
+    }
 }
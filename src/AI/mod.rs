@@ -1 +1,4 @@
-pub mod minimax;
\ No newline at end of file
+pub mod eval;
+pub mod minimax;
+pub mod tt;
+pub mod tune;
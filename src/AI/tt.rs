@@ -0,0 +1,165 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::bitboard::square;
+use crate::chessboard::Move;
+
+// The transposition table remembers what the search learned about positions
+// it has seen, keyed by Zobrist hash. The same position is often reached by
+// different move orders, and each iteration of iterative deepening revisits
+// the positions of the last one, so a stored result can end a search early
+// or at least say which move to try first.
+//
+// Search threads share one table without locking. Every slot is two atomic
+// words, the entry packed into one and the hash XORed with it in the other.
+// A slot torn by two threads writing at once then fails the hash check and
+// reads as empty, instead of mixing two entries.
+
+// How a stored score relates to the position's true value. A cutoff means
+// the search stopped looking once the score was good enough, so it is only
+// a bound.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower, // the true score is at least this (failed high)
+    Upper, // the true score is at most this (failed low)
+}
+
+// A move reduced to its squares and promotion, which is all it takes to
+// find it again among the moves of the same position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackedMove(u16);
+
+impl PackedMove {
+    pub fn new(m: &Move) -> Self {
+        let from = square(m.from.0, m.from.1) as u16;
+        let to = square(m.to.0, m.to.1) as u16;
+        let promotion = m.promotion.map_or(0, |kind| kind.index() as u16 + 1);
+        PackedMove(from | to << 6 | promotion << 12)
+    }
+
+    pub fn matches(&self, m: &Move) -> bool {
+        *self == PackedMove::new(m)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    pub hash: u64,
+    pub depth: i32,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<PackedMove>,
+}
+
+impl Entry {
+    // The score in the low 32 bits, then the depth, the bound and the move
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        let best_move = self.best_move.map_or(0, |m| m.0 as u64 | 1 << 15);
+        let depth = self.depth.clamp(-128, 127) as i8 as u8 as u64;
+        self.score as u32 as u64 | depth << 32 | bound << 40 | best_move << 42
+    }
+
+    fn unpack(hash: u64, data: u64) -> Option<Entry> {
+        let bound = match data >> 40 & 3 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => return None, // an empty slot
+        };
+        let best_move = (data >> 42) as u16;
+        Some(Entry {
+            hash,
+            depth: (data >> 32) as u8 as i8 as i32,
+            score: data as u32 as i32,
+            bound,
+            best_move: (best_move & 1 << 15 != 0).then_some(PackedMove(best_move & 0x7FFF)),
+        })
+    }
+}
+
+pub const DEFAULT_SIZE_MB: usize = 16;
+
+struct Slot {
+    check: AtomicU64, // hash ^ data
+    data: AtomicU64,
+}
+
+// A fixed number of slots, each holding one entry. A new entry replaces the
+// old one unless that was searched deeper for the same position.
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+}
+
+impl TranspositionTable {
+    // Zero megabytes makes a table of a single slot, for searches that barely use it
+    pub fn new(megabytes: usize) -> Self {
+        let count = (megabytes * 1024 * 1024 / size_of::<Slot>()).max(1);
+        let slots = (0..count).map(|_| Slot { check: AtomicU64::new(0), data: AtomicU64::new(0) }).collect();
+        TranspositionTable { slots }
+    }
+
+    // Forget everything, e.g. for a new game
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn slot(&self, hash: u64) -> &Slot {
+        &self.slots[(hash % self.slots.len() as u64) as usize]
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<Entry> {
+        let slot = self.slot(hash);
+        let data = slot.data.load(Ordering::Relaxed);
+        if slot.check.load(Ordering::Relaxed) ^ data != hash {
+            return None;
+        }
+        Entry::unpack(hash, data)
+    }
+
+    pub fn store(&self, entry: Entry) {
+        if self.probe(entry.hash).is_some_and(|old| old.depth > entry.depth) {
+            return;
+        }
+        let slot = self.slot(entry.hash);
+        let data = entry.pack();
+        slot.check.store(entry.hash ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIZE_MB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chessboard::Board;
+
+    #[test]
+    fn entries_survive_packing() {
+        let table = TranspositionTable::new(1);
+        let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let knight = board.parse_uci("b7b8n").unwrap();
+        let queen = board.parse_uci("b7b8q").unwrap();
+        for (depth, score, bound) in [(5, -123, Bound::Exact), (-2, 99_990, Bound::Lower), (127, -99_990, Bound::Upper)] {
+            table.clear();
+            table.store(Entry { hash: 42, depth, score, bound, best_move: Some(PackedMove::new(&knight)) });
+            let entry = table.probe(42).unwrap();
+            assert_eq!((entry.depth, entry.score, entry.bound), (depth, score, bound));
+            assert!(entry.best_move.unwrap().matches(&knight));
+            assert!(!entry.best_move.unwrap().matches(&queen));
+        }
+        assert!(table.probe(43).is_none());
+    }
+}
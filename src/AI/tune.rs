@@ -0,0 +1,258 @@
+use serde_json::Value;
+
+use crate::chessboard::{Board, FenError};
+use super::eval::{evaluate, EvalWeights};
+use super::minimax::quiescence;
+
+// Texel tuning: fit the evaluation weights to the results of real games.
+// Each position is labeled with how its game ended (1 for a white win, 0.5
+// for a draw, 0 for a loss). A sigmoid turns the evaluation into an
+// expected result, and the weights are nudged one at a time, keeping every
+// change that lowers the mean squared difference from the labels.
+//
+// The evaluation is only meaningful in quiet positions, so each position is
+// first played through the captures its quiescence search picks. Those
+// quiet positions are then evaluated statically while tuning, which is
+// far cheaper than searching them for every trial weight.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TuneError {
+    InvalidFen { line: usize, error: FenError },
+    MissingResult(usize), // the line number
+}
+
+impl std::fmt::Display for TuneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TuneError::InvalidFen { line, error } => write!(f, "line {}: {}", line, error),
+            TuneError::MissingResult(line) => write!(f, "line {}: no game result", line),
+        }
+    }
+}
+
+impl std::error::Error for TuneError {}
+
+pub struct LabeledPosition {
+    pub board: Board,
+    pub result: f64, // from white's point of view
+}
+
+// One position per line: a FEN followed by the result, written as 1-0, 0-1
+// or 1/2-1/2 (optionally quoted, as in `c9 "1-0";`) or as a number in
+// brackets such as [0.5]. Blank lines and lines starting with # are skipped.
+pub fn parse_positions(text: &str) -> Result<Vec<LabeledPosition>, TuneError> {
+    let mut positions = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let result = parse_result(line).ok_or(TuneError::MissingResult(i + 1))?;
+        // The move counters are optional and some formats put other fields
+        // in their place, so fall back to the first four fields
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let board = Board::from_fen(&fields[..fields.len().min(6)].join(" "))
+            .or_else(|_| Board::from_fen(&fields[..fields.len().min(4)].join(" ")))
+            .map_err(|error| TuneError::InvalidFen { line: i + 1, error })?;
+        positions.push(LabeledPosition { board, result });
+    }
+    Ok(positions)
+}
+
+fn parse_result(line: &str) -> Option<f64> {
+    if let Some(start) = line.rfind('[') {
+        let end = line[start..].find(']')? + start;
+        return line[start + 1..end].trim().parse().ok().filter(|r| (0.0..=1.0).contains(r));
+    }
+    line.split_whitespace()
+        .skip(4)
+        .map(|token| token.trim_matches(|c| c == '"' || c == ';'))
+        .find_map(|token| match token {
+            "1-0" => Some(1.0),
+            "0-1" => Some(0.0),
+            "1/2-1/2" => Some(0.5),
+            _ => None,
+        })
+}
+
+// Every weight as one flat list, in a fixed order, so the tuner can work
+// through them without knowing the struct's layout
+pub fn params(weights: &EvalWeights) -> Vec<i32> {
+    fn collect(value: &Value, out: &mut Vec<i32>) {
+        match value {
+            Value::Number(n) => out.push(n.as_i64().unwrap_or(0) as i32),
+            Value::Array(items) => items.iter().for_each(|item| collect(item, out)),
+            Value::Object(fields) => fields.values().for_each(|field| collect(field, out)),
+            _ => {}
+        }
+    }
+    let mut out = Vec::new();
+    collect(&serde_json::to_value(weights).expect("weights serialize"), &mut out);
+    out
+}
+
+// The inverse of `params`
+pub fn with_params(weights: &EvalWeights, params: &[i32]) -> EvalWeights {
+    fn replace(value: &mut Value, params: &mut std::slice::Iter<i32>) {
+        match value {
+            Value::Number(n) => *n = (*params.next().expect("one param per weight")).into(),
+            Value::Array(items) => items.iter_mut().for_each(|item| replace(item, params)),
+            Value::Object(fields) => fields.values_mut().for_each(|field| replace(field, params)),
+            _ => {}
+        }
+    }
+    let mut value = serde_json::to_value(weights).expect("weights serialize");
+    replace(&mut value, &mut params.iter());
+    serde_json::from_value(value).expect("same shape as the weights")
+}
+
+pub struct Tuner {
+    positions: Vec<Board>, // the quiet positions
+    results: Vec<f64>,
+    pub k: f64, // scales centipawns into the sigmoid
+}
+
+impl Tuner {
+    pub fn new(positions: Vec<LabeledPosition>, weights: &EvalWeights) -> Self {
+        let mut quiet = Vec::with_capacity(positions.len());
+        let mut results = Vec::with_capacity(positions.len());
+        for position in positions {
+            let mut board = position.board;
+            for m in quiescence(&board, weights).1 {
+                board.apply_move(m);
+            }
+            quiet.push(board);
+            results.push(position.result);
+        }
+        Tuner { positions: quiet, results, k: 1.0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    // Mean squared difference between the results and the results the
+    // evaluation predicts, spread over every core
+    pub fn error(&self, weights: &EvalWeights) -> f64 {
+        if self.positions.is_empty() {
+            return 0.0;
+        }
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk = self.positions.len().div_ceil(threads);
+        let total: f64 = std::thread::scope(|scope| {
+            let workers: Vec<_> = self
+                .positions
+                .chunks(chunk)
+                .zip(self.results.chunks(chunk))
+                .map(|(positions, results)| {
+                    scope.spawn(move || {
+                        positions
+                            .iter()
+                            .zip(results)
+                            .map(|(board, result)| (result - sigmoid(evaluate(board, weights), self.k)).powi(2))
+                            .sum::<f64>()
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|worker| worker.join().expect("error worker panicked")).sum()
+        });
+        total / self.positions.len() as f64
+    }
+
+    // Picks the K that makes the current weights fit best, so that tuning
+    // changes the weights rather than just their scale
+    pub fn fit_k(&mut self, weights: &EvalWeights) -> f64 {
+        let mut best = (self.k, self.error(weights));
+        let mut step = 1.0;
+        for _ in 0..10 {
+            let center = best.0;
+            for i in -10..=10 {
+                self.k = (center + i as f64 * step).max(0.0);
+                let error = self.error(weights);
+                if error < best.1 {
+                    best = (self.k, error);
+                }
+            }
+            step /= 10.0;
+        }
+        self.k = best.0;
+        best.1
+    }
+
+    // One pass of local search: each weight is tried `step` up and then
+    // down, keeping whichever lowers the error. Returns the new error.
+    pub fn tune_pass(&self, weights: &mut EvalWeights, step: i32) -> f64 {
+        let mut current = params(weights);
+        let mut best_error = self.error(weights);
+        for i in 0..current.len() {
+            for delta in [step, -step] {
+                current[i] += delta;
+                let trial = with_params(weights, &current);
+                let error = self.error(&trial);
+                if error < best_error {
+                    best_error = error;
+                    *weights = trial;
+                    break;
+                }
+                current[i] -= delta;
+            }
+        }
+        best_error
+    }
+}
+
+// Expected result for white from a centipawn score
+pub fn sigmoid(score: i32, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score as f64 / 400.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_labeled_positions() {
+        let text = "\
+# a comment
+rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1 [1.0]
+rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - c9 \"1/2-1/2\";
+
+4k3/8/8/8/8/8/8/4K3 w - - 0 1 0-1
+";
+        let positions = parse_positions(text).unwrap();
+        let results: Vec<f64> = positions.iter().map(|p| p.result).collect();
+        assert_eq!(results, [1.0, 0.5, 0.0]);
+        assert_eq!(positions[0].board.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert_eq!(parse_positions("4k3/8/8/8/8/8/8/4K3 w - - 0 1").err(), Some(TuneError::MissingResult(1)));
+    }
+
+    #[test]
+    fn params_round_trip() {
+        let weights = EvalWeights::default();
+        let mut list = params(&weights);
+        assert_eq!(with_params(&weights, &list), weights);
+        let i = list.iter().position(|&p| p == weights.bishop_pair.0).unwrap();
+        list[i] += 7;
+        assert_ne!(with_params(&weights, &list), weights);
+    }
+
+    #[test]
+    fn tuning_lowers_the_error() {
+        // White is a knight up and won, black is a pawn up and drew
+        let text = "\
+4k3/8/8/8/8/2N5/8/4K3 w - - 0 1 [1.0]
+4k3/8/8/8/8/2N5/8/4K3 b - - 0 1 [1.0]
+4k3/p7/8/8/8/8/8/4K3 w - - 0 1 [0.5]
+4k3/p7/8/8/8/8/8/4K3 b - - 0 1 [0.5]
+";
+        let mut weights = EvalWeights::default();
+        let mut tuner = Tuner::new(parse_positions(text).unwrap(), &weights);
+        let before = tuner.fit_k(&weights);
+        let after = tuner.tune_pass(&mut weights, 5);
+        assert!(after < before);
+    }
+}
@@ -0,0 +1,57 @@
+use std::time::Instant;
+
+use chess::AI::eval::EvalWeights;
+use chess::AI::tune::{parse_positions, Tuner};
+
+// Texel tuner for the evaluation weights:
+//
+//     tune <positions file> [--eval start.toml] [--out tuned.toml] [--passes N] [--step N]
+//
+// Each line of the positions file holds a FEN and the game's result (see
+// AI::tune). The weights are saved after every pass, so the tuner can be
+// stopped at any time and restarted from its output with --eval.
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let option = |name: &str| args.iter().position(|a| a == name).and_then(|i| args.get(i + 1));
+    let Some(path) = args.first().filter(|a| !a.starts_with("--")) else {
+        eprintln!("usage: tune <positions file> [--eval start.toml] [--out tuned.toml] [--passes N] [--step N]");
+        std::process::exit(2);
+    };
+    let out = option("--out").map_or("tuned.toml", |s| s.as_str());
+    let passes: usize = option("--passes").and_then(|n| n.parse().ok()).unwrap_or(100);
+    let step: i32 = option("--step").and_then(|n| n.parse().ok()).unwrap_or(1);
+
+    let mut weights = match option("--eval").map(EvalWeights::load) {
+        Some(Ok(weights)) => weights,
+        Some(Err(e)) => fail(&format!("could not load weights: {}", e)),
+        None => EvalWeights::default(),
+    };
+    let text = std::fs::read_to_string(path).unwrap_or_else(|e| fail(&format!("could not read {}: {}", path, e)));
+    let positions = parse_positions(&text).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+
+    let start = Instant::now();
+    let mut tuner = Tuner::new(positions, &weights);
+    if tuner.is_empty() {
+        fail(&format!("no positions in {}", path));
+    }
+    let mut error = tuner.fit_k(&weights);
+    println!("{} positions, K = {:.3}, error {:.6}", tuner.len(), tuner.k, error);
+
+    for pass in 1..=passes {
+        let new_error = tuner.tune_pass(&mut weights, step);
+        if let Err(e) = weights.save(out) {
+            fail(&format!("could not save {}: {}", out, e));
+        }
+        println!("pass {}: error {:.6} ({:.0}s), saved {}", pass, new_error, start.elapsed().as_secs_f64(), out);
+        if new_error >= error {
+            break;
+        }
+        error = new_error;
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("tune: {}", message);
+    std::process::exit(1);
+}
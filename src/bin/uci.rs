@@ -0,0 +1,305 @@
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use chess::chessboard::Board;
+use chess::Color;
+use chess::AI::eval::EvalWeights;
+use chess::AI::minimax::{iterative_deepening, mate_in, SearchLimits, SearchResult};
+use chess::AI::tt::{TranspositionTable, DEFAULT_SIZE_MB};
+
+// Minimal UCI front end for the engine. Commands are read from stdin on the
+// main thread while `go` runs the search on a worker thread, so commands are
+// answered during a search. Any command that changes the engine's state
+// stops a running search first, and closing stdin counts as `quit`. The
+// transposition table lives across searches until `ucinewgame`.
+//
+// `uci --eval <file>` starts with evaluation weights from a TOML or JSON
+// file; the EvalFile option switches them later.
+
+fn main() {
+    let stdin = io::stdin();
+    let mut board = Board::new();
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut options = Options {
+        tt: Arc::new(TranspositionTable::default()),
+        weights: Arc::new(EvalWeights::default()),
+        threads: 1,
+    };
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(path) = args.iter().position(|a| a == "--eval").and_then(|i| args.get(i + 1)) {
+        match EvalWeights::load(path) {
+            Ok(loaded) => options.weights = Arc::new(loaded),
+            Err(e) => println!("info string could not load {}: {}", path, e),
+        }
+    }
+    let mut search_thread: Option<JoinHandle<()>> = None;
+
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else { break };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some(&command) = tokens.first() else { continue };
+
+        match command {
+            "uci" => {
+                println!("id name Chess");
+                println!("id author ViktorErik");
+                println!("option name Hash type spin default {} min 1 max 4096", DEFAULT_SIZE_MB);
+                println!("option name Threads type spin default 1 min 1 max {}", MAX_THREADS);
+                println!("option name EvalFile type string default <empty>");
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "setoption" => {
+                stop_search(&mut search_thread, &stop);
+                set_option(&tokens[1..], &mut options);
+            }
+            "ucinewgame" => {
+                stop_search(&mut search_thread, &stop);
+                board = Board::new();
+                options.tt.clear();
+            }
+            "position" => {
+                stop_search(&mut search_thread, &stop);
+                match set_position(&tokens[1..]) {
+                    Some(b) => board = b,
+                    None => println!("info string invalid position: {}", line),
+                }
+            }
+            "go" => {
+                stop_search(&mut search_thread, &stop);
+                let limits = parse_go(&tokens[1..], board.get_current_turn() == Color::White, Arc::clone(&stop));
+                let search_board = board.clone();
+                let options = options.clone();
+                search_thread = Some(std::thread::spawn(move || {
+                    search(&search_board, &limits, &options);
+                }));
+            }
+            "stop" => stop_search(&mut search_thread, &stop),
+            "bench" => {
+                stop_search(&mut search_thread, &stop);
+                let depth = tokens.get(1).and_then(|d| d.parse().ok()).unwrap_or(5);
+                bench(depth, &options);
+            }
+            "quit" => {
+                stop_search(&mut search_thread, &stop);
+                break;
+            }
+            _ => println!("info string unknown command: {}", command),
+        }
+        io::stdout().flush().ok();
+    }
+    // Input closed without `quit`
+    stop_search(&mut search_thread, &stop);
+}
+
+// Stops a running search and waits for it to print its best move. Waiting
+// without stopping could hang forever on `go infinite`, which only ends on
+// `stop`, a command this thread would never get to read.
+fn stop_search(search_thread: &mut Option<JoinHandle<()>>, stop: &AtomicBool) {
+    if let Some(handle) = search_thread.take() {
+        stop.store(true, Ordering::Relaxed);
+        handle.join().ok();
+    }
+    stop.store(false, Ordering::Relaxed);
+}
+
+const MAX_THREADS: usize = 256;
+
+// Settings that carry over from one search to the next. The table and
+// weights are shared with the search thread, which never outlives a change.
+#[derive(Clone)]
+struct Options {
+    tt: Arc<TranspositionTable>,
+    weights: Arc<EvalWeights>,
+    threads: usize,
+}
+
+// setoption name <name> [value <value>]
+fn set_option(args: &[&str], options: &mut Options) {
+    let value_at = args.iter().position(|&t| t == "value").unwrap_or(args.len());
+    let name = args.get(1..value_at).unwrap_or_default().join(" ");
+    let value = args.get(value_at + 1..).unwrap_or_default().join(" ");
+    match name.to_ascii_lowercase().as_str() {
+        "hash" => match value.parse::<usize>() {
+            Ok(megabytes) => options.tt = Arc::new(TranspositionTable::new(megabytes.clamp(1, 4096))),
+            Err(_) => println!("info string invalid Hash value: {}", value),
+        },
+        // Scores stored under the old weights would mislead the new ones
+        "evalfile" => match EvalWeights::load(&value) {
+            Ok(loaded) => {
+                options.weights = Arc::new(loaded);
+                options.tt.clear();
+            }
+            Err(e) => println!("info string could not load {}: {}", value, e),
+        },
+        "threads" => match value.parse::<usize>() {
+            Ok(threads) => options.threads = threads.clamp(1, MAX_THREADS),
+            Err(_) => println!("info string invalid Threads value: {}", value),
+        },
+        _ => println!("info string unknown option: {}", name),
+    }
+}
+
+// position [startpos | fen <fen>] [moves <move>...]
+fn set_position(args: &[&str]) -> Option<Board> {
+    let moves_at = args.iter().position(|&t| t == "moves").unwrap_or(args.len());
+    let mut board = match args.first() {
+        Some(&"startpos") => Board::new(),
+        Some(&"fen") => Board::from_fen(&args[1..moves_at].join(" ")).ok()?,
+        _ => return None,
+    };
+    for text in args.iter().skip(moves_at + 1) {
+        let m = board.parse_uci(text).ok()?;
+        board.apply_move(m);
+    }
+    Some(board)
+}
+
+// go [depth N] [nodes N] [movetime ms] [wtime ms] [btime ms] [winc ms] [binc ms] [infinite]
+fn parse_go(args: &[&str], white_to_move: bool, stop: Arc<AtomicBool>) -> SearchLimits {
+    let mut limits = SearchLimits { stop: Some(stop), ..Default::default() };
+    let mut movetime = None;
+    let mut time_left = None;
+    let mut increment = 0;
+    let mut i = 0;
+    while i < args.len() {
+        let value = args.get(i + 1).and_then(|v| v.parse::<u64>().ok());
+        match args[i] {
+            "depth" => limits.depth = value.map(|d| (d as i32).max(1)),
+            "nodes" => limits.nodes = value,
+            "movetime" => movetime = value,
+            "wtime" if white_to_move => time_left = value,
+            "btime" if !white_to_move => time_left = value,
+            "winc" if white_to_move => increment = value.unwrap_or(0),
+            "binc" if !white_to_move => increment = value.unwrap_or(0),
+            _ => {}
+        }
+        i += 1;
+    }
+
+    // Spend a small slice of the remaining clock plus most of the increment,
+    // but never the whole clock: keep a margin for reporting the move
+    limits.time = match (movetime, time_left) {
+        (Some(ms), _) => Some(Duration::from_millis(ms)),
+        (None, Some(ms)) => Some(Duration::from_millis((ms / 30 + increment * 3 / 4).min(ms.saturating_sub(50)))),
+        (None, None) => None,
+    };
+    limits
+}
+
+// Reports every completed iteration, then the best move once a limit or
+// `stop` ends the search
+fn search(board: &Board, limits: &SearchLimits, options: &Options) {
+    let result = iterative_deepening(board, limits, &options.tt, &options.weights, options.threads, |iteration| {
+        let pv: Vec<String> = iteration.pv.iter().map(|m| m.to_uci()).collect();
+        let score = match mate_in(iteration.score) {
+            Some(moves) => format!("mate {}", moves),
+            None => format!("cp {}", iteration.score),
+        };
+        let millis = iteration.time.as_millis();
+        println!(
+            "info depth {} score {} nodes {} nps {} time {} pv {}",
+            iteration.depth,
+            score,
+            iteration.nodes,
+            iteration.nodes as u128 * 1000 / millis.max(1),
+            millis,
+            pv.join(" ")
+        );
+        io::stdout().flush().ok();
+    });
+
+    // Wait for `stop` when searching without limits, as UCI requires
+    let unlimited = limits.depth.is_none() && limits.time.is_none() && limits.nodes.is_none();
+    if let Some(stop) = limits.stop.as_ref().filter(|_| unlimited) {
+        while !stop.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    print_stats(&result);
+    match result.best_move {
+        Some(m) => println!("bestmove {}", m.to_uci()),
+        None => println!("bestmove 0000"),
+    }
+    io::stdout().flush().ok();
+}
+
+// How well moves were ordered: a good order finds most cutoffs on the first move
+fn print_stats(result: &SearchResult) {
+    let stats = result.stats;
+    println!(
+        "info string nodes {} tt hits {} cutoffs {} first move cutoffs {:.1}%",
+        result.nodes,
+        stats.tt_hits,
+        stats.cutoffs,
+        stats.first_move_cutoffs as f64 * 100.0 / stats.cutoffs.max(1) as f64
+    );
+}
+
+// bench [depth]: searches a fixed set of positions with fresh tables and
+// prints the node total, for comparing search changes
+fn bench(depth: i32, options: &Options) {
+    let positions = [
+        chess::chessboard::START_FEN,
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    ];
+    let start = Instant::now();
+    let mut nodes = 0;
+    for fen in positions {
+        let board = Board::from_fen(fen).expect("bench positions are valid");
+        let tt = TranspositionTable::default();
+        let result = iterative_deepening(&board, &SearchLimits::depth(depth), &tt, &options.weights, options.threads, |_| {});
+        print_stats(&result);
+        nodes += result.nodes;
+    }
+    let millis = start.elapsed().as_millis();
+    println!("info string bench depth {} nodes {} time {} nps {}", depth, nodes, millis, nodes as u128 * 1000 / millis.max(1));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_go_limits() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let limits = parse_go(&["depth", "5", "nodes", "1000"], true, Arc::clone(&stop));
+        assert_eq!((limits.depth, limits.nodes, limits.time), (Some(5), Some(1000), None));
+        assert!(limits.stop.is_some());
+
+        let limits = parse_go(&["movetime", "300", "wtime", "60000"], true, Arc::clone(&stop));
+        assert_eq!(limits.time, Some(Duration::from_millis(300)));
+
+        // Only the clock of the side to move counts
+        let limits = parse_go(&["wtime", "60000", "btime", "3000", "winc", "1000", "binc", "0"], false, Arc::clone(&stop));
+        assert_eq!(limits.time, Some(Duration::from_millis(100)));
+
+        // A large increment never spends more than is left
+        let limits = parse_go(&["wtime", "500", "winc", "2000"], true, Arc::clone(&stop));
+        assert_eq!(limits.time, Some(Duration::from_millis(450)));
+
+        let limits = parse_go(&["infinite"], true, stop);
+        assert_eq!((limits.depth, limits.nodes, limits.time), (None, None, None));
+    }
+
+    #[test]
+    fn sets_positions() {
+        let board = set_position(&["startpos", "moves", "e2e4", "e7e5"]).unwrap();
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+
+        let board = set_position(&["fen", "4k3/8/8/8/8/8/8/4K2R", "w", "K", "-", "0", "1", "moves", "e1g1"]).unwrap();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+
+        assert!(set_position(&["startpos", "moves", "e2e5"]).is_none());
+        assert!(set_position(&["fen", "not", "a", "fen"]).is_none());
+        assert!(set_position(&[]).is_none());
+    }
+}
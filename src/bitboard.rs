@@ -0,0 +1,106 @@
+// Bitboard helpers. A bitboard is a u64 with one bit per square, numbered
+// rank * 8 + file, so a1 = 0, h1 = 7, a8 = 56 and h8 = 63.
+
+pub fn square(rank: i32, file: i32) -> usize {
+    (rank * 8 + file) as usize
+}
+
+pub fn rank_file(square: usize) -> (i32, i32) {
+    ((square / 8) as i32, (square % 8) as i32)
+}
+
+pub fn bit(square: usize) -> u64 {
+    1u64 << square
+}
+
+// a1 is a dark square, so these are a1, c1, ..., b2, d2, ... h8
+pub const DARK_SQUARES: u64 = 0xAA55_AA55_AA55_AA55;
+
+// Iterates over the squares set in a bitboard, lowest first
+pub struct Squares(pub u64);
+
+impl Iterator for Squares {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+}
+
+// Attack table for a piece that jumps by fixed (rank, file) steps, like the knight and king
+pub const fn leaper_table(steps: &[(i32, i32)]) -> [u64; 64] {
+    let mut table = [0u64; 64];
+    let mut sq = 0;
+    while sq < 64 {
+        let rank = (sq / 8) as i32;
+        let file = (sq % 8) as i32;
+        let mut i = 0;
+        while i < steps.len() {
+            let r = rank + steps[i].0;
+            let f = file + steps[i].1;
+            if r >= 0 && r < 8 && f >= 0 && f < 8 {
+                table[sq] |= 1u64 << (r * 8 + f);
+            }
+            i += 1;
+        }
+        sq += 1;
+    }
+    table
+}
+
+// Slider directions as (rank, file) steps. The first four move towards higher
+// square numbers, the last four towards lower ones.
+pub const NORTH: usize = 0;
+pub const NORTH_EAST: usize = 1;
+pub const EAST: usize = 2;
+pub const NORTH_WEST: usize = 3;
+pub const SOUTH: usize = 4;
+pub const SOUTH_WEST: usize = 5;
+pub const WEST: usize = 6;
+pub const SOUTH_EAST: usize = 7;
+
+const DIRECTIONS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (1, -1), (-1, 0), (-1, -1), (0, -1), (-1, 1)];
+
+// RAYS[direction][square] holds every square from `square` to the edge of the board
+const RAYS: [[u64; 64]; 8] = ray_table();
+
+const fn ray_table() -> [[u64; 64]; 8] {
+    let mut table = [[0u64; 64]; 8];
+    let mut dir = 0;
+    while dir < 8 {
+        let mut sq = 0;
+        while sq < 64 {
+            let mut r = (sq / 8) as i32 + DIRECTIONS[dir].0;
+            let mut f = (sq % 8) as i32 + DIRECTIONS[dir].1;
+            while r >= 0 && r < 8 && f >= 0 && f < 8 {
+                table[dir][sq] |= 1u64 << (r * 8 + f);
+                r += DIRECTIONS[dir].0;
+                f += DIRECTIONS[dir].1;
+            }
+            sq += 1;
+        }
+        dir += 1;
+    }
+    table
+}
+
+// Squares a slider on `square` reaches in one direction, up to and including
+// the first occupied square
+pub fn ray_attacks(square: usize, direction: usize, occupied: u64) -> u64 {
+    let ray = RAYS[direction][square];
+    let blockers = ray & occupied;
+    if blockers == 0 {
+        return ray;
+    }
+    let first = if direction < SOUTH {
+        blockers.trailing_zeros() as usize
+    } else {
+        63 - blockers.leading_zeros() as usize
+    };
+    ray ^ RAYS[direction][first]
+}
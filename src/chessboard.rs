@@ -1,439 +1,511 @@
-use macroquad::prelude::*;
-use macroquad::texture::DrawTextureParams;
-use crate::pieces::{rook::Rook, pawn::Pawn, knight::Knight, bishop::Bishop, king::King, queen::Queen};
-use crate::pieces::piece::Piece;
+use crate::bitboard::{bit, rank_file, square, Squares, DARK_SQUARES};
+use crate::pieces::{bishop, king, knight, pawn, queen, rook};
+use crate::pieces::piece::{Color, PieceKind};
+use crate::zobrist;
 
 
 // PIECES ARE: R, N, B, K, Q, P
 // RANKS: 1-8
 // FILES: A-H
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    MissingField(&'static str),
+    InvalidPlacement(String),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidCounter(String),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::MissingField(field) => write!(f, "FEN is missing the {} field", field),
+            FenError::InvalidPlacement(msg) => write!(f, "invalid piece placement: {}", msg),
+            FenError::InvalidSideToMove(s) => write!(f, "invalid side to move '{}'", s),
+            FenError::InvalidCastling(s) => write!(f, "invalid castling rights '{}'", s),
+            FenError::InvalidEnPassant(s) => write!(f, "invalid en passant square '{}'", s),
+            FenError::InvalidCounter(s) => write!(f, "invalid move counter '{}'", s),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+// Where the game stands for the side to move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameStatus {
+    Ongoing,
+    Checkmate(Color), // the winner
+    Stalemate,
+    FiftyMoveRule,
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    InsufficientMaterial,
+}
+
+impl std::fmt::Display for GameStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameStatus::Ongoing => write!(f, "game in progress"),
+            GameStatus::Checkmate(winner) => write!(f, "checkmate, {} wins", winner),
+            GameStatus::Stalemate => write!(f, "stalemate, draw"),
+            GameStatus::FiftyMoveRule => write!(f, "draw by the fifty-move rule"),
+            GameStatus::ThreefoldRepetition => write!(f, "draw by threefold repetition"),
+            GameStatus::FivefoldRepetition => write!(f, "draw by fivefold repetition"),
+            GameStatus::InsufficientMaterial => write!(f, "draw by insufficient material"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveFlag {
+    Normal,
+    DoublePush,
+    EnPassant,
+    Castle, // the king's move; the rook follows in apply_move
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Move {
+    pub from: (i32, i32), // (rank, file)
+    pub to: (i32, i32),
+    pub piece: PieceKind,
+    pub captured: Option<PieceKind>, // a pawn for en passant, even though `to` is empty
+    pub promotion: Option<PieceKind>, // a promoting pawn becomes a queen when None
+    pub flag: MoveFlag,
+}
+
+impl Move {
+    pub fn is_capture(&self) -> bool {
+        self.captured.is_some()
+    }
+
+    // What stands on `to` afterwards: the promotion choice for a pawn reaching the last rank
+    pub fn placed_piece(&self) -> PieceKind {
+        if self.piece == PieceKind::Pawn && (self.to.0 == 0 || self.to.0 == 7) {
+            self.promotion.unwrap_or(PieceKind::Queen)
+        } else {
+            self.piece
+        }
+    }
+}
+
+// Board state that a Move alone cannot restore, captured by apply_move for unmake_move
+#[derive(Clone, Copy, Debug)]
+pub struct UndoInfo {
+    last_double_pawn_file: Option<i32>,
+    white_king_moved: bool,
+    white_rook_a_moved: bool,
+    white_rook_h_moved: bool,
+    black_king_moved: bool,
+    black_rook_a_moved: bool,
+    black_rook_h_moved: bool,
+    halfmove_clock: u32,
+    hash: u64,
+}
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Clone)]
 pub struct Board {
-    square_size: f32,
-    pub pieces: Vec<Box<dyn Piece>>,
-    selected_piece: Option<usize>, // index in pieces vector
-    possible_moves: Vec<(i32, i32)>,
-    current_turn: String,
-    pub last_double_pawn_file: Option<i32>,
-    pub white_king_moved: bool,
-    pub white_rook_a_moved: bool,
-    pub white_rook_h_moved: bool,
-    pub black_king_moved: bool,
-    pub black_rook_a_moved: bool,
-    pub black_rook_h_moved: bool,
-    pub promotion_state: Option<usize>, // piece index to promote
+    bitboards: [[u64; 6]; 2], // [color][kind], see Color::index and PieceKind::index
+    occupancy: [u64; 2], // every piece of each color
+    current_turn: Color,
+    // En passant and castling rights are part of the hash, so only moves may change them
+    last_double_pawn_file: Option<i32>, // file of a pawn that just moved two squares
+    white_king_moved: bool,
+    white_rook_a_moved: bool,
+    white_rook_h_moved: bool,
+    black_king_moved: bool,
+    black_rook_a_moved: bool,
+    black_rook_h_moved: bool,
+    pub halfmove_clock: u32, // plies since the last capture or pawn move
+    pub fullmove_number: u32,
+    hash: u64, // Zobrist hash, kept up to date by put_piece, remove_piece and apply_move
+    history: Vec<u64>, // hash of every position reached, the current one last
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Board {
 
     pub fn new() -> Self {
-        Self {
-            square_size: screen_width() / 8.0,
-            // pieces: vec!["R".to_string()],
-            pieces: vec![
-                Box::new(Rook::new("white", 0, 0)), 
-                Box::new(Knight::new("white", 0, 1)), 
-                Box::new(Bishop::new("white", 0, 2)), 
-                Box::new(Queen::new("white", 0, 3)), 
-                Box::new(King::new("white", 0, 4)), 
-                Box::new(Bishop::new("white", 0, 5)), 
-                Box::new(Knight::new("white", 0, 6)), 
-                Box::new(Rook::new("white", 0, 7)), 
-
-                Box::new(Pawn::new("white", 1, 0)),
-                Box::new(Pawn::new("white", 1, 1)),
-                Box::new(Pawn::new("white", 1, 2)),
-                Box::new(Pawn::new("white", 1, 3)),
-                Box::new(Pawn::new("white", 1, 4)),
-                Box::new(Pawn::new("white", 1, 5)),
-                Box::new(Pawn::new("white", 1, 6)),
-                Box::new(Pawn::new("white", 1, 7)),
-
-                Box::new(Rook::new("black", 7, 0)), 
-                Box::new(Knight::new("black", 7, 1)), 
-                Box::new(Bishop::new("black", 7, 2)), 
-                Box::new(Queen::new("black", 7, 3)), 
-                Box::new(King::new("black", 7, 4)), 
-                Box::new(Bishop::new("black", 7, 5)), 
-                Box::new(Knight::new("black", 7, 6)), 
-                Box::new(Rook::new("black", 7, 7)), 
-
-                Box::new(Pawn::new("black", 6, 0)),
-                Box::new(Pawn::new("black", 6, 1)),
-                Box::new(Pawn::new("black", 6, 2)),
-                Box::new(Pawn::new("black", 6, 3)),
-                Box::new(Pawn::new("black", 6, 4)),
-                Box::new(Pawn::new("black", 6, 5)),
-                Box::new(Pawn::new("black", 6, 6)),
-                Box::new(Pawn::new("black", 6, 7)),
-            ],
-            selected_piece: None,
-            possible_moves: Vec::new(),
-            current_turn: "white".to_string(),
-            last_double_pawn_file: None,
-            white_king_moved: false,
-            white_rook_a_moved: false,
-            white_rook_h_moved: false,
-            black_king_moved: false,
-            black_rook_a_moved: false,
-            black_rook_h_moved: false,
-            promotion_state: None,
-        }        
-    }
-
-    pub fn draw_pieces(&self, textures: &std::collections::HashMap<String, macroquad::texture::Texture2D>) {
-        for p in &self.pieces {
-            let piece_type = match p.get_name() {
-                "P" => "pawn",
-                "R" => "rook",
-                "N" => "knight",
-                "B" => "bishop",
-                "Q" => "queen",
-                "K" => "king",
-                _ => "pawn",
-            };
-            let key = format!("{}-{}", p.get_color(), piece_type);
-            if let Some(tex) = textures.get(&key) {
-                let scale = 0.75;
-                let w = tex.width() as f32 * scale;
-                let h = tex.height() as f32 * scale;
-                let x = (p.get_file() as f32) * self.square_size + (self.square_size - w) / 2.0;
-                let y = (7 - p.get_rank()) as f32 * self.square_size + (self.square_size - h) / 2.0;
-                draw_texture_ex(tex, x, y, WHITE, DrawTextureParams {
-                    dest_size: Some(Vec2::new(w, h)),
-                    ..Default::default()
-                });
-            }
-        }
+        Board::from_fen(START_FEN).expect("the starting position is valid")
     }
-   
-    pub fn draw_squares(&self) {        
-        for r in 0..8 {
-            for c in (r%2..8).step_by(2) {
-                let y = (7 - r) as f32 * self.square_size;
-                draw_rectangle((c as f32) * self.square_size, y, self.square_size, self.square_size, BEIGE);
-            }
+
+    // Bitboard of one color's pieces of one kind
+    pub fn pieces(&self, color: Color, kind: PieceKind) -> u64 {
+        self.bitboards[color.index()][kind.index()]
+    }
+
+    // Bitboard of every piece of one color
+    pub fn occupancy(&self, color: Color) -> u64 {
+        self.occupancy[color.index()]
+    }
+
+    pub fn occupied(&self) -> u64 {
+        self.occupancy(Color::White) | self.occupancy(Color::Black)
+    }
+
+    // The piece on (rank, file), if any
+    pub fn piece_at(&self, (rank, file): (i32, i32)) -> Option<(Color, PieceKind)> {
+        let sq = square(rank, file);
+        let color = Color::ALL.into_iter().find(|&c| self.occupancy(c) & bit(sq) != 0)?;
+        let kind = self.kind_at(color, sq)?;
+        Some((color, kind))
+    }
+
+    fn kind_at(&self, color: Color, sq: usize) -> Option<PieceKind> {
+        PieceKind::ALL.into_iter().find(|&kind| self.pieces(color, kind) & bit(sq) != 0)
+    }
+
+    fn put_piece(&mut self, color: Color, kind: PieceKind, sq: usize) {
+        self.bitboards[color.index()][kind.index()] |= bit(sq);
+        self.occupancy[color.index()] |= bit(sq);
+        self.hash ^= zobrist::piece(color.index(), kind.index(), sq);
+    }
+
+    fn remove_piece(&mut self, color: Color, kind: PieceKind, sq: usize) {
+        self.bitboards[color.index()][kind.index()] &= !bit(sq);
+        self.occupancy[color.index()] &= !bit(sq);
+        self.hash ^= zobrist::piece(color.index(), kind.index(), sq);
+    }
+
+    // Whether any piece of `by` attacks the square, looking outward from it
+    fn is_square_attacked(&self, sq: usize, by: Color) -> bool {
+        let occupied = self.occupied();
+        let queens = self.pieces(by, PieceKind::Queen);
+        pawn::ATTACKS[by.opponent().index()][sq] & self.pieces(by, PieceKind::Pawn) != 0
+            || knight::ATTACKS[sq] & self.pieces(by, PieceKind::Knight) != 0
+            || king::ATTACKS[sq] & self.pieces(by, PieceKind::King) != 0
+            || bishop::attacks(sq, occupied) & (self.pieces(by, PieceKind::Bishop) | queens) != 0
+            || rook::attacks(sq, occupied) & (self.pieces(by, PieceKind::Rook) | queens) != 0
+    }
+
+    pub fn is_in_check(&self, color: Color) -> bool {
+        Squares(self.pieces(color, PieceKind::King)).any(|sq| self.is_square_attacked(sq, color.opponent()))
+    }
+
+    // Legal moves of the piece on `from`
+    pub fn get_legal_moves(&self, from: (i32, i32)) -> Vec<Move> {
+        self.get_all_legal_moves().into_iter().filter(|m| m.from == from).collect()
+    }
+
+    pub fn get_current_turn(&self) -> Color {
+        self.current_turn
+    }
+
+    // The file of a pawn that just moved two squares and may be taken en passant
+    pub fn en_passant_file(&self) -> Option<i32> {
+        self.last_double_pawn_file
+    }
+
+    // Whether a side's king has moved, losing both its castling rights
+    pub fn king_moved(&self, color: Color) -> bool {
+        match color {
+            Color::White => self.white_king_moved,
+            Color::Black => self.black_king_moved,
         }
+    }
 
-        for r in 0..8 {
-            for c in ((r+1)%2..8).step_by(2) {
-                let y = (7 - r) as f32 * self.square_size;
-                draw_rectangle((c as f32) * self.square_size, y, self.square_size, self.square_size, WHITE);
-            }
+    // Whether the rook that started on the a-file (0) or h-file (7) has moved
+    pub fn rook_moved(&self, color: Color, file: i32) -> bool {
+        match (color, file == 0) {
+            (Color::White, true) => self.white_rook_a_moved,
+            (Color::White, false) => self.white_rook_h_moved,
+            (Color::Black, true) => self.black_rook_a_moved,
+            (Color::Black, false) => self.black_rook_h_moved,
         }
+    }
 
+    // Hands the move to the other side; the fullmove number advances after black moves
+    fn switch_turn(&mut self) {
+        if self.current_turn == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.current_turn = self.current_turn.opponent();
+        self.hash ^= zobrist::side();
+    }
 
-        // Highlight possible moves
-        for &(rank, file) in &self.possible_moves {
-            let y = (7 - rank) as f32 * self.square_size;
-            draw_rectangle(file as f32 * self.square_size, y, self.square_size, self.square_size, YELLOW);
-        }
-        // Highlight selected piece
-        if let Some(index) = self.selected_piece {
-            let p = &self.pieces[index];
-            let y = (7 - p.get_rank()) as f32 * self.square_size;
-            draw_rectangle(p.get_file() as f32 * self.square_size, y, self.square_size, self.square_size, BLUE);
-        }
-    }
-
-    pub fn mark_piece(&mut self, x: f32, y: f32) {
-        let file = (x / self.square_size) as i32;
-        let rank = 7 - (y / self.square_size) as i32; // since rank 0 is bottom
-        if file >= 0 && file < 8 && rank >= 0 && rank < 8 {
-            // If a piece is selected, try to move it
-            if let Some(selected_index) = self.selected_piece {
-                if self.possible_moves.contains(&(rank, file)) {
-                    let from_rank = self.pieces[selected_index].get_rank();
-                    let from_file = self.pieces[selected_index].get_file();
-                    let piece_name = self.pieces[selected_index].get_name().to_string();
-                    let piece_color = self.pieces[selected_index].get_color().to_string();
-                    let mut adjusted_selected = selected_index;
-                    
-                    // Handle castling
-                    if piece_name == "K" && (file - from_file).abs() == 2 {
-                        // Move the rook
-                        if file > from_file {
-                            // Kingside castling
-                            if let Some(rook_index) = self.pieces.iter().position(|p| p.get_rank() == from_rank && p.get_file() == 7 && p.get_name() == "R") {
-                                self.pieces[rook_index].set_file(5);
-                            }
-                        } else {
-                            // Queenside castling
-                            if let Some(rook_index) = self.pieces.iter().position(|p| p.get_rank() == from_rank && p.get_file() == 0 && p.get_name() == "R") {
-                                self.pieces[rook_index].set_file(3);
-                            }
-                        }
-                        // Mark king and rook as moved
-                        if piece_color == "white" {
-                            self.white_king_moved = true;
-                            if file > from_file {
-                                self.white_rook_h_moved = true;
-                            } else {
-                                self.white_rook_a_moved = true;
-                            }
-                        } else {
-                            self.black_king_moved = true;
-                            if file > from_file {
-                                self.black_rook_h_moved = true;
-                            } else {
-                                self.black_rook_a_moved = true;
-                            }
-                        }
-                    }
-                    
-                    // Find and remove captured piece
-                    if let Some(captured_index) = self.pieces.iter().position(|p| p.get_rank() == rank && p.get_file() == file) {
-                        self.pieces.remove(captured_index);
-                        // Adjust selected_index if the captured piece was before it
-                        if captured_index < adjusted_selected {
-                            adjusted_selected -= 1;
-                        }
-                    }
-                    // Handle en passant capture
-                    if piece_name == "P" && file != from_file && self.pieces.iter().find(|p| p.get_rank() == rank && p.get_file() == file).is_none() {
-                        // En passant: remove the captured pawn
-                        if let Some(en_passant_index) = self.pieces.iter().position(|p| p.get_rank() == from_rank && p.get_file() == file) {
-                            self.pieces.remove(en_passant_index);
-                            if en_passant_index < adjusted_selected {
-                                adjusted_selected -= 1;
-                            }
-                        }
-                    }
-                    // Move the piece
-                    {
-                        let selected_piece = &mut self.pieces[adjusted_selected];
-                        selected_piece.set_rank(rank);
-                        selected_piece.set_file(file);
-                    }
-                    
-                    // Track if king or rook moved
-                    if piece_name == "K" {
-                        if piece_color == "white" {
-                            self.white_king_moved = true;
-                        } else {
-                            self.black_king_moved = true;
-                        }
-                    } else if piece_name == "R" {
-                        if piece_color == "white" {
-                            if from_file == 0 {
-                                self.white_rook_a_moved = true;
-                            } else if from_file == 7 {
-                                self.white_rook_h_moved = true;
-                            }
-                        } else {
-                            if from_file == 0 {
-                                self.black_rook_a_moved = true;
-                            } else if from_file == 7 {
-                                self.black_rook_h_moved = true;
-                            }
-                        }
-                    }
-                    
-                    // Set last double pawn move
-                    if piece_name == "P" && (rank - from_rank).abs() == 2 {
-                        self.last_double_pawn_file = Some(file);
-                    } else {
-                        self.last_double_pawn_file = None;
-                    }
-                    // Deselect
-                    self.selected_piece = None;
-                    self.possible_moves.clear();
-                    
-                    // Check for pawn promotion
-                    if piece_name == "P" && ((piece_color == "white" && rank == 7) || (piece_color == "black" && rank == 0)) {
-                        self.promotion_state = Some(adjusted_selected);
-                        return;
-                    }
-                    
-                    // Switch turns
-                    self.current_turn = if self.current_turn == "white" { "black".to_string() } else { "white".to_string() };
-                    return;
-                } else {
-                    // Clicked elsewhere, deselect
-                    self.selected_piece = None;
-                    self.possible_moves.clear();
-                    return;
-                }
-            }
-            // No piece selected, try to select one
-            for (i, p) in self.pieces.iter().enumerate() {
-                if p.get_rank() == rank && p.get_file() == file && p.get_color() == self.current_turn {
-                    self.selected_piece = Some(i);
-                    self.possible_moves = self.get_legal_moves(i);
-                    return;
-                }
-            }
+    // Without a legal move the side to move is mated if in check, stalemated
+    // otherwise; mate on the hundredth ply still wins, so it is checked first
+    pub fn status(&self) -> GameStatus {
+        if self.get_all_legal_moves().is_empty() {
+            return if self.is_in_check(self.current_turn) {
+                GameStatus::Checkmate(self.current_turn.opponent())
+            } else {
+                GameStatus::Stalemate
+            };
+        }
+        let repetitions = self.repetition_count();
+        if self.is_insufficient_material() {
+            GameStatus::InsufficientMaterial
+        } else if repetitions >= 5 {
+            GameStatus::FivefoldRepetition
+        } else if repetitions >= 3 {
+            GameStatus::ThreefoldRepetition
+        } else if self.halfmove_clock >= 100 {
+            GameStatus::FiftyMoveRule
+        } else {
+            GameStatus::Ongoing
         }
     }
-    pub fn is_in_check(&self, color: &str) -> bool {
-        // Find king position
-        let king_pos = self.pieces.iter().find(|p| p.get_name() == "K" && p.get_color() == color).map(|p| (p.get_rank(), p.get_file()));
-        if let Some((kr, kf)) = king_pos {
-            // Check if any enemy piece can move to king_pos
-            for p in &self.pieces {
-                if p.get_color() != color {
-                    let moves = p.get_possible_moves(self);
-                    if moves.contains(&(kr, kf)) {
-                        return true;
-                    }
+
+    // How many times the current position has occurred, counting this time.
+    // Captures and pawn moves can't be undone, so only look back that far.
+    pub fn repetition_count(&self) -> usize {
+        let Some(current) = self.history.last() else { return 1 };
+        let reversible = (self.halfmove_clock as usize + 1).min(self.history.len());
+        self.history.iter().rev().take(reversible).filter(|&key| key == current).count()
+    }
+
+    // Identifies the position for repetitions and the search's transposition
+    // table: equal positions always hash the same, different ones almost never
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    // The hash worked out from scratch rather than kept up to date move by move
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+        for color in Color::ALL {
+            for kind in PieceKind::ALL {
+                for sq in Squares(self.pieces(color, kind)) {
+                    hash ^= zobrist::piece(color.index(), kind.index(), sq);
                 }
             }
         }
-        false
-    }
-
-    pub fn get_legal_moves(&mut self, piece_index: usize) -> Vec<(i32, i32)> {
-        let possible = self.pieces[piece_index].get_possible_moves(self);
-        // Always filter moves that would leave the king in check
-        let mut legal = Vec::new();
-        // Save positions
-        let saved_positions: Vec<(i32, i32)> = self.pieces.iter().map(|p| (p.get_rank(), p.get_file())).collect();
-        for &(r, f) in &possible {
-            let piece_name = self.pieces[piece_index].get_name().to_string();
-            let from_file = self.pieces[piece_index].get_file();
-            
-            // Check for castling and validate castling rules
-            if piece_name == "K" && (f - from_file).abs() == 2 {
-                // Castling move - check intermediate squares are not in check
-                let between_file = (from_file + f) / 2;
-                
-                // Simulate king on intermediate square
-                self.pieces[piece_index].set_rank(r);
-                self.pieces[piece_index].set_file(between_file);
-                if self.is_in_check(&self.current_turn) {
-                    // Intermediate square is in check, can't castle
-                    self.pieces[piece_index].set_rank(saved_positions[piece_index].0);
-                    self.pieces[piece_index].set_file(saved_positions[piece_index].1);
-                    continue;
-                }
-                
-                // Simulate king on final square
-                self.pieces[piece_index].set_file(f);
-                if self.is_in_check(&self.current_turn) {
-                    // Final square is in check, can't castle
-                    self.pieces[piece_index].set_rank(saved_positions[piece_index].0);
-                    self.pieces[piece_index].set_file(saved_positions[piece_index].1);
-                    continue;
-                }
-                
-                legal.push((r, f));
-                self.pieces[piece_index].set_rank(saved_positions[piece_index].0);
-                self.pieces[piece_index].set_file(saved_positions[piece_index].1);
-            } else {
-                // Regular move - simulate and check
-                // Simulate move
-                self.pieces[piece_index].set_rank(r);
-                self.pieces[piece_index].set_file(f);
-                // Remove captured if any
-                let mut captured_idx = None;
-                for (i, p) in self.pieces.iter().enumerate() {
-                    if i != piece_index && p.get_rank() == r && p.get_file() == f {
-                        captured_idx = Some(i);
-                        // Temporarily move captured away
-                        self.pieces[i].set_rank(-1);
-                        self.pieces[i].set_file(-1);
-                        break;
-                    }
-                }
-                // Check if king is in check after move
-                if !self.is_in_check(&self.current_turn) {
-                    legal.push((r, f));
-                }
-                // Restore
-                if let Some(idx) = captured_idx {
-                    self.pieces[idx].set_rank(saved_positions[idx].0);
-                    self.pieces[idx].set_file(saved_positions[idx].1);
-                }
-                self.pieces[piece_index].set_rank(saved_positions[piece_index].0);
-                self.pieces[piece_index].set_file(saved_positions[piece_index].1);
-            }
+        if self.current_turn == Color::Black {
+            hash ^= zobrist::side();
         }
-        legal
+        hash ^ self.rights_hash()
+    }
+
+    // The part of the hash for castling rights and en passant, which
+    // apply_move swaps out as a whole. Positions are the same for the
+    // repetition rules only if both match, and an en passant file only
+    // counts if one of our pawns stands beside it.
+    fn rights_hash(&self) -> u64 {
+        let rights = [
+            !self.white_king_moved && !self.white_rook_h_moved,
+            !self.white_king_moved && !self.white_rook_a_moved,
+            !self.black_king_moved && !self.black_rook_h_moved,
+            !self.black_king_moved && !self.black_rook_a_moved,
+        ];
+        let index = rights.iter().enumerate().filter(|&(_, &right)| right).map(|(i, _)| 1 << i).sum();
+        let side = self.current_turn;
+        let en_passant = self.last_double_pawn_file.filter(|&file| {
+            let target = square(if side == Color::White { 5 } else { 2 }, file);
+            pawn::ATTACKS[side.opponent().index()][target] & self.pieces(side, PieceKind::Pawn) != 0
+        });
+        zobrist::castling(index) ^ en_passant.map_or(0, zobrist::en_passant)
     }
 
-    pub fn get_current_turn(&self) -> &str {
-        &self.current_turn
+    // Neither side can mate: bare kings, a single minor piece, or only
+    // bishops that all stand on squares of one color
+    pub fn is_insufficient_material(&self) -> bool {
+        let heavy = [PieceKind::Pawn, PieceKind::Rook, PieceKind::Queen];
+        if Color::ALL.iter().any(|&c| heavy.iter().any(|&kind| self.pieces(c, kind) != 0)) {
+            return false;
+        }
+        let knights = self.pieces(Color::White, PieceKind::Knight) | self.pieces(Color::Black, PieceKind::Knight);
+        let bishops = self.pieces(Color::White, PieceKind::Bishop) | self.pieces(Color::Black, PieceKind::Bishop);
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+        knights == 0 && (bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0)
     }
 
     pub fn is_game_over(&self) -> bool {
-        let moves = self.get_all_moves(&self.current_turn);
-        moves.is_empty()
-    }
-
-    pub fn get_all_moves(&self, color: &str) -> Vec<((i32, i32), (i32, i32))> {
-        let mut all_moves = Vec::new();
-        for p in &self.pieces {
-            if p.get_color() == color {
-                let from = (p.get_rank(), p.get_file());
-                let possible = p.get_possible_moves(self);
-                for to in possible {
-                    all_moves.push((from, to));
+        self.status() != GameStatus::Ongoing
+    }
+
+    pub fn get_all_moves(&self, color: Color) -> Vec<Move> {
+        let mut all_moves = Vec::with_capacity(64);
+        for kind in PieceKind::ALL {
+            for from in Squares(self.pieces(color, kind)) {
+                let targets = match kind {
+                    PieceKind::Pawn => pawn::get_possible_moves(self, from, color),
+                    PieceKind::Knight => knight::get_possible_moves(self, from, color),
+                    PieceKind::Bishop => bishop::get_possible_moves(self, from, color),
+                    PieceKind::Rook => rook::get_possible_moves(self, from, color),
+                    PieceKind::Queen => queen::get_possible_moves(self, from, color),
+                    PieceKind::King => king::get_possible_moves(self, from, color),
+                };
+                for to in Squares(targets) {
+                    self.push_moves(color, kind, from, to, &mut all_moves);
                 }
             }
         }
         all_moves
     }
 
-    pub fn make_move(&mut self, m: ((i32, i32), (i32, i32))) {
-        let ((from_rank, from_file), (to_rank, to_file)) = m;
-        if let Some(piece_index) = self.pieces.iter().position(|p| p.get_rank() == from_rank && p.get_file() == from_file) {
-            let mut adjusted_index = piece_index;
-            
-            // Remove captured piece if any
-            if let Some(captured_index) = self.pieces.iter().position(|p| p.get_rank() == to_rank && p.get_file() == to_file) {
-                self.pieces.remove(captured_index);
-                // Adjust index if the captured piece was before it
-                if captured_index < adjusted_index {
-                    adjusted_index -= 1;
-                }
-            }
-            // Handle en passant
-            if self.pieces[adjusted_index].get_name() == "P" && from_file != to_file && self.pieces.iter().find(|p| p.get_rank() == to_rank && p.get_file() == to_file).is_none() {
-                // Remove the captured pawn
-                if let Some(en_index) = self.pieces.iter().position(|p| p.get_rank() == from_rank && p.get_file() == to_file) {
-                    self.pieces.remove(en_index);
-                    if en_index < adjusted_index {
-                        adjusted_index -= 1;
-                    }
-                }
+    // Like `get_all_moves` for the side to move, but only moves that keep the king safe
+    pub fn get_all_legal_moves(&self) -> Vec<Move> {
+        let mut scratch = self.clone();
+        let mover = self.current_turn;
+        let mut all_moves = self.get_all_moves(mover);
+        all_moves.retain(|m| {
+            if scratch.castles_through_check(m) {
+                return false;
             }
-            // Move the piece
-            self.pieces[adjusted_index].set_rank(to_rank);
-            self.pieces[adjusted_index].set_file(to_file);
-            // Update last_double_pawn_file
-            if self.pieces[adjusted_index].get_name() == "P" && (to_rank - from_rank).abs() == 2 {
-                self.last_double_pawn_file = Some(to_file);
-            } else {
-                self.last_double_pawn_file = None;
+            let undo = scratch.apply_move(*m);
+            let legal = !scratch.is_in_check(mover);
+            scratch.unmake_move(m, &undo);
+            legal
+        });
+        all_moves
+    }
+
+    // The king may not castle out of or through check; landing in check is
+    // caught like any other move that leaves the king attacked
+    pub fn castles_through_check(&self, m: &Move) -> bool {
+        if m.flag != MoveFlag::Castle {
+            return false;
+        }
+        let between = square(m.from.0, (m.from.1 + m.to.1) / 2);
+        self.is_in_check(self.current_turn) || self.is_square_attacked(between, self.current_turn.opponent())
+    }
+
+    // Describes a piece's move to `to`, once per promotion choice for a pawn reaching the last rank
+    fn push_moves(&self, color: Color, kind: PieceKind, from: usize, to: usize, moves: &mut Vec<Move>) {
+        let mut captured = self.kind_at(color.opponent(), to);
+        let ((from_rank, from_file), (to_rank, to_file)) = (rank_file(from), rank_file(to));
+        let mut flag = MoveFlag::Normal;
+        if kind == PieceKind::King && (to_file - from_file).abs() == 2 {
+            flag = MoveFlag::Castle;
+        } else if kind == PieceKind::Pawn && (to_rank - from_rank).abs() == 2 {
+            flag = MoveFlag::DoublePush;
+        } else if kind == PieceKind::Pawn && to_file != from_file && captured.is_none() {
+            flag = MoveFlag::EnPassant;
+            captured = Some(PieceKind::Pawn);
+        }
+
+        let m = Move { from: (from_rank, from_file), to: (to_rank, to_file), piece: kind, captured, promotion: None, flag };
+        if kind == PieceKind::Pawn && (to_rank == 0 || to_rank == 7) {
+            for promotion in [PieceKind::Queen, PieceKind::Rook, PieceKind::Bishop, PieceKind::Knight] {
+                moves.push(Move { promotion: Some(promotion), ..m });
             }
-            // Switch turns
-            self.current_turn = if self.current_turn == "white" { "black".to_string() } else { "white".to_string() };
+        } else {
+            moves.push(m);
         }
     }
 
-    pub fn position_key(&self) -> String {
-        // Create an 8x8 board representation; empty squares '.'
-        let mut board = vec![vec!['.'; 8]; 8];
-        for p in &self.pieces {
-            let r = p.get_rank() as usize;
-            let f = p.get_file() as usize;
-            let ch = p.get_name().chars().next().unwrap_or(' ');
-            let ch = if p.get_color() == "white" { ch } else { ch.to_ascii_lowercase() };
-            if r < 8 && f < 8 {
-                board[r][f] = ch;
+    // The one place moves are played, for clicks and search alike
+    pub fn apply_move(&mut self, m: Move) -> UndoInfo {
+        let undo = UndoInfo {
+            last_double_pawn_file: self.last_double_pawn_file,
+            white_king_moved: self.white_king_moved,
+            white_rook_a_moved: self.white_rook_a_moved,
+            white_rook_h_moved: self.white_rook_h_moved,
+            black_king_moved: self.black_king_moved,
+            black_rook_a_moved: self.black_rook_a_moved,
+            black_rook_h_moved: self.black_rook_h_moved,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+        };
+        self.hash ^= self.rights_hash();
+        let ((from_rank, from_file), (to_rank, to_file)) = (m.from, m.to);
+        let (from, to) = (square(from_rank, from_file), square(to_rank, to_file));
+        let mover = self.current_turn;
+
+        // Remove the captured piece, which sits beside the pawn for en passant
+        if let Some(captured) = m.captured {
+            let captured_square = if m.flag == MoveFlag::EnPassant { square(from_rank, to_file) } else { to };
+            self.remove_piece(mover.opponent(), captured, captured_square);
+        }
+        // Move the piece, promoting a pawn on the last rank to a queen unless told otherwise
+        self.remove_piece(mover, m.piece, from);
+        self.put_piece(mover, m.placed_piece(), to);
+        // Handle castling: the rook jumps to the other side of the king
+        if m.flag == MoveFlag::Castle {
+            let (rook_from, rook_to) = if to_file > from_file { (7, 5) } else { (0, 3) };
+            self.remove_piece(mover, PieceKind::Rook, square(from_rank, rook_from));
+            self.put_piece(mover, PieceKind::Rook, square(from_rank, rook_to));
+        }
+
+        // A king move gives up both castling rights
+        if m.piece == PieceKind::King {
+            match mover {
+                Color::White => self.white_king_moved = true,
+                Color::Black => self.black_king_moved = true,
             }
         }
-        // Serialize rows from rank 7 to 0 so orientation matches display
-        let mut s = String::new();
-        for rank in (0..8).rev() {
-            for file in 0..8 {
-                s.push(board[rank][file]);
+        // Anything leaving or landing on a corner means that rook moved or was captured
+        for corner in [m.from, m.to] {
+            match corner {
+                (0, 0) => self.white_rook_a_moved = true,
+                (0, 7) => self.white_rook_h_moved = true,
+                (7, 0) => self.black_rook_a_moved = true,
+                (7, 7) => self.black_rook_h_moved = true,
+                _ => {}
             }
         }
-        // add side to move
-        s.push_str(" ");
-        s.push_str(&self.current_turn);
-        // add castling rights
+
+        // Update last_double_pawn_file
+        if m.flag == MoveFlag::DoublePush {
+            self.last_double_pawn_file = Some(to_file);
+        } else {
+            self.last_double_pawn_file = None;
+        }
+        // Pawn moves and captures reset the fifty-move counter
+        if m.piece == PieceKind::Pawn || m.is_capture() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        // Switch turns
+        self.switch_turn();
+        self.hash ^= self.rights_hash();
+        self.history.push(self.hash);
+        undo
+    }
+
+    // Takes back `m`, which must be the last move played with the `undo` it returned
+    pub fn unmake_move(&mut self, m: &Move, undo: &UndoInfo) {
+        let ((from_rank, from_file), (to_rank, to_file)) = (m.from, m.to);
+        self.history.pop();
+        if self.current_turn == Color::White {
+            self.fullmove_number -= 1;
+        }
+        self.current_turn = self.current_turn.opponent();
+        let mover = self.current_turn;
+        let (from, to) = (square(from_rank, from_file), square(to_rank, to_file));
+
+        // Move the piece back, turning a promoted piece into a pawn again
+        self.remove_piece(mover, m.placed_piece(), to);
+        self.put_piece(mover, m.piece, from);
+        // Put a castled rook back in its corner
+        if m.flag == MoveFlag::Castle {
+            let (rook_from, rook_to) = if to_file > from_file { (7, 5) } else { (0, 3) };
+            self.remove_piece(mover, PieceKind::Rook, square(from_rank, rook_to));
+            self.put_piece(mover, PieceKind::Rook, square(from_rank, rook_from));
+        }
+        // Restore the captured piece, beside the pawn for en passant
+        if let Some(captured) = m.captured {
+            let captured_square = if m.flag == MoveFlag::EnPassant { square(from_rank, to_file) } else { to };
+            self.put_piece(mover.opponent(), captured, captured_square);
+        }
+
+        self.last_double_pawn_file = undo.last_double_pawn_file;
+        self.white_king_moved = undo.white_king_moved;
+        self.white_rook_a_moved = undo.white_rook_a_moved;
+        self.white_rook_h_moved = undo.white_rook_h_moved;
+        self.black_king_moved = undo.black_king_moved;
+        self.black_rook_a_moved = undo.black_rook_a_moved;
+        self.black_rook_h_moved = undo.black_rook_h_moved;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.hash = undo.hash;
+    }
+
+    // Castling rights in FEN form, e.g. "KQkq" or "-"
+    fn castling_rights(&self) -> String {
         let mut cast = String::new();
         if !self.white_king_moved {
             if !self.white_rook_h_moved { cast.push('K'); }
@@ -444,73 +516,273 @@ impl Board {
             if !self.black_rook_a_moved { cast.push('q'); }
         }
         if cast.is_empty() { cast.push('-'); }
-        s.push_str(" ");
-        s.push_str(&cast);
-        // add en passant file or -
-        s.push_str(" ");
-        if let Some(f) = self.last_double_pawn_file {
-            s.push_str(&f.to_string());
+        cast
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Board, FenError> {
+        let mut fields = fen.split_whitespace();
+        let placement = fields.next().ok_or(FenError::MissingField("piece placement"))?;
+        let side = fields.next().ok_or(FenError::MissingField("side to move"))?;
+        let castling = fields.next().ok_or(FenError::MissingField("castling"))?;
+        let en_passant = fields.next().ok_or(FenError::MissingField("en passant"))?;
+        // The move counters are often left out, so fall back to a fresh game
+        let halfmove = fields.next().unwrap_or("0");
+        let fullmove = fields.next().unwrap_or("1");
+
+        let mut board = Board {
+            bitboards: [[0; 6]; 2],
+            occupancy: [0; 2],
+            current_turn: Color::White,
+            last_double_pawn_file: None,
+            white_king_moved: false,
+            white_rook_a_moved: false,
+            white_rook_h_moved: false,
+            black_king_moved: false,
+            black_rook_a_moved: false,
+            black_rook_h_moved: false,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
+            history: Vec::new(),
+        };
+
+        // Placement lists ranks 8 down to 1, files a to h within each rank
+        let rows: Vec<&str> = placement.split('/').collect();
+        if rows.len() != 8 {
+            return Err(FenError::InvalidPlacement(format!("expected 8 ranks, found {}", rows.len())));
+        }
+        for (i, row) in rows.iter().enumerate() {
+            let rank = 7 - i as i32;
+            let mut file = 0;
+            for ch in row.chars() {
+                if let Some(empty) = ch.to_digit(10) {
+                    if !(1..=8).contains(&empty) {
+                        return Err(FenError::InvalidPlacement(format!("'{}' is not a count of empty squares", ch)));
+                    }
+                    file += empty as i32;
+                    continue;
+                }
+                if file > 7 {
+                    return Err(FenError::InvalidPlacement(format!("rank {} has more than 8 files", rank + 1)));
+                }
+                let color = if ch.is_ascii_uppercase() { Color::White } else { Color::Black };
+                let Some(kind) = PieceKind::from_letter(ch) else {
+                    return Err(FenError::InvalidPlacement(format!("unknown piece '{}'", ch)));
+                };
+                board.put_piece(color, kind, square(rank, file));
+                file += 1;
+            }
+            if file != 8 {
+                return Err(FenError::InvalidPlacement(format!("rank {} does not cover 8 files", rank + 1)));
+            }
+        }
+        for color in Color::ALL {
+            if board.pieces(color, PieceKind::King).count_ones() != 1 {
+                return Err(FenError::InvalidPlacement(format!("{} must have exactly one king", color)));
+            }
+        }
+        // Pawns promote on the last rank and can never stand on their first
+        let back_ranks = 0xFF00_0000_0000_00FF;
+        if (board.pieces(Color::White, PieceKind::Pawn) | board.pieces(Color::Black, PieceKind::Pawn)) & back_ranks != 0 {
+            return Err(FenError::InvalidPlacement("pawn on the first or last rank".to_string()));
+        }
+
+        board.current_turn = match side {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(FenError::InvalidSideToMove(side.to_string())),
+        };
+        // Otherwise the side to move could take the king
+        if board.is_in_check(board.current_turn.opponent()) {
+            return Err(FenError::InvalidPlacement(format!("{} is in check but not to move", board.current_turn.opponent())));
+        }
+
+        if castling.is_empty() || (castling != "-" && !castling.chars().all(|c| "KQkq".contains(c))) {
+            return Err(FenError::InvalidCastling(castling.to_string()));
+        }
+        // A right that is absent means the king or that rook has moved
+        let white_kingside = castling.contains('K');
+        let white_queenside = castling.contains('Q');
+        let black_kingside = castling.contains('k');
+        let black_queenside = castling.contains('q');
+        board.white_king_moved = !white_kingside && !white_queenside;
+        board.white_rook_a_moved = !white_queenside;
+        board.white_rook_h_moved = !white_kingside;
+        board.black_king_moved = !black_kingside && !black_queenside;
+        board.black_rook_a_moved = !black_queenside;
+        board.black_rook_h_moved = !black_kingside;
+        // A right needs the king and that rook on their home squares, or the
+        // same position would hash differently depending on its FEN
+        for (letter, color, rook_file) in [('K', Color::White, 7), ('Q', Color::White, 0), ('k', Color::Black, 7), ('q', Color::Black, 0)] {
+            let rank = if color == Color::White { 0 } else { 7 };
+            let home = board.piece_at((rank, 4)) == Some((color, PieceKind::King))
+                && board.piece_at((rank, rook_file)) == Some((color, PieceKind::Rook));
+            if castling.contains(letter) && !home {
+                return Err(FenError::InvalidCastling(castling.to_string()));
+            }
+        }
+
+        board.last_double_pawn_file = if en_passant == "-" {
+            None
         } else {
-            s.push('-');
-        }
-        s
-    }
-
-    pub fn promote_piece(&mut self, piece_index: usize, piece_type: char) {
-        let color = self.pieces[piece_index].get_color().to_string();
-        let rank = self.pieces[piece_index].get_rank();
-        let file = self.pieces[piece_index].get_file();
-        
-        // Remove the pawn
-        self.pieces.remove(piece_index);
-        
-        // Add the new piece
-        let new_piece: Box<dyn Piece> = match piece_type {
-            'Q' => Box::new(Queen::new(&color, rank, file)),
-            'R' => Box::new(Rook::new(&color, rank, file)),
-            'B' => Box::new(Bishop::new(&color, rank, file)),
-            'N' => Box::new(Knight::new(&color, rank, file)),
-            _ => Box::new(Queen::new(&color, rank, file)),
+            let bytes = en_passant.as_bytes();
+            let expected_rank = if board.current_turn == Color::White { b'6' } else { b'3' };
+            if bytes.len() != 2 || !(b'a'..=b'h').contains(&bytes[0]) || bytes[1] != expected_rank {
+                return Err(FenError::InvalidEnPassant(en_passant.to_string()));
+            }
+            // The pawn that just moved two squares must be there, with the
+            // squares it passed over empty
+            let file = (bytes[0] - b'a') as i32;
+            let (pawn_rank, skipped, start) = if board.current_turn == Color::White { (4, 5, 6) } else { (3, 2, 1) };
+            let mover = board.current_turn.opponent();
+            if board.piece_at((pawn_rank, file)) != Some((mover, PieceKind::Pawn))
+                || board.piece_at((skipped, file)).is_some()
+                || board.piece_at((start, file)).is_some()
+            {
+                return Err(FenError::InvalidEnPassant(en_passant.to_string()));
+            }
+            Some(file)
+        };
+
+        board.halfmove_clock = halfmove.parse::<u32>().map_err(|_| FenError::InvalidCounter(halfmove.to_string()))?;
+        board.fullmove_number = match fullmove.parse::<u32>() {
+            Ok(n) if n >= 1 => n,
+            _ => return Err(FenError::InvalidCounter(fullmove.to_string())),
         };
-        self.pieces.push(new_piece);
-        
-        // Clear promotion state and switch turns
-        self.promotion_state = None;
-        self.selected_piece = None;
-        self.possible_moves.clear();
-        self.current_turn = if self.current_turn == "white" { "black".to_string() } else { "white".to_string() };
+        board.hash = board.compute_hash();
+        board.history.push(board.hash);
+        Ok(board)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.piece_at((rank, file)) {
+                    Some((color, kind)) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(fen_letter(color, kind));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push(' ');
+        fen.push(if self.current_turn == Color::White { 'w' } else { 'b' });
+        fen.push(' ');
+        fen.push_str(&self.castling_rights());
+
+        // The en passant target is the square the double-pushed pawn skipped over
+        fen.push(' ');
+        match self.last_double_pawn_file {
+            Some(f) => {
+                fen.push((b'a' + f as u8) as char);
+                fen.push(if self.current_turn == Color::White { '6' } else { '3' });
+            }
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
+        fen
     }
 }
 
-impl Clone for Board {
-    fn clone(&self) -> Self {
-        let mut pieces = Vec::new();
-        for p in &self.pieces {
-            let new_piece: Box<dyn Piece> = match p.get_name() {
-                "P" => Box::new(crate::pieces::pawn::Pawn::new(p.get_color(), p.get_rank(), p.get_file())),
-                "R" => Box::new(crate::pieces::rook::Rook::new(p.get_color(), p.get_rank(), p.get_file())),
-                "N" => Box::new(crate::pieces::knight::Knight::new(p.get_color(), p.get_rank(), p.get_file())),
-                "B" => Box::new(crate::pieces::bishop::Bishop::new(p.get_color(), p.get_rank(), p.get_file())),
-                "Q" => Box::new(crate::pieces::queen::Queen::new(p.get_color(), p.get_rank(), p.get_file())),
-                "K" => Box::new(crate::pieces::king::King::new(p.get_color(), p.get_rank(), p.get_file())),
-                _ => Box::new(crate::pieces::pawn::Pawn::new(p.get_color(), p.get_rank(), p.get_file())),
-            };
-            pieces.push(new_piece);
-        }
-        Board {
-            square_size: self.square_size,
-            pieces,
-            selected_piece: self.selected_piece,
-            possible_moves: self.possible_moves.clone(),
-            current_turn: self.current_turn.clone(),
-            last_double_pawn_file: self.last_double_pawn_file,
-            white_king_moved: self.white_king_moved,
-            white_rook_a_moved: self.white_rook_a_moved,
-            white_rook_h_moved: self.white_rook_h_moved,
-            black_king_moved: self.black_king_moved,
-            black_rook_a_moved: self.black_rook_a_moved,
-            black_rook_h_moved: self.black_rook_h_moved,
-            promotion_state: self.promotion_state,
+// White pieces are uppercase in FEN, black lowercase
+fn fen_letter(color: Color, kind: PieceKind) -> char {
+    match color {
+        Color::White => kind.letter(),
+        Color::Black => kind.letter().to_ascii_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fen_round_trip() {
+        for fen in [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 12 40",
+        ] {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rejects_impossible_positions() {
+        for fen in [
+            "rnbqkbnrQ/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", // nine files
+            "P3k3/8/8/8/8/8/8/4K3 w - - 0 1", // pawn on the last rank
+            "4k3/8/8/8/8/8/8/p3K3 b - - 0 1", // pawn on the first rank
+            "4k3/8/8/8/8/8/8/4R1K1 w - - 0 1", // black in check with white to move
+        ] {
+            assert!(matches!(Board::from_fen(fen), Err(FenError::InvalidPlacement(_))), "{}", fen);
+        }
+        assert!(matches!(Board::from_fen("4k3/8/8/8/8/8/8/40K3 w - - 0 1"), Err(FenError::InvalidPlacement(_))));
+        // Castling without the king and rook at home, en passant without the pawn
+        assert!(matches!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w K - 0 1"), Err(FenError::InvalidCastling(_))));
+        assert!(matches!(Board::from_fen("r3k3/8/8/8/8/8/8/4K3 w k - 0 1"), Err(FenError::InvalidCastling(_))));
+        assert!(matches!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - e6 0 1"), Err(FenError::InvalidEnPassant(_))));
+        assert!(Board::from_fen("4k3/8/8/4p3/8/8/8/4K3 w - e6 0 1").is_ok());
+    }
+
+    #[test]
+    fn tells_checkmate_from_stalemate() {
+        let fools_mate = Board::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        assert_eq!(fools_mate.status(), GameStatus::Checkmate(Color::Black));
+        let stalemate = Board::from_fen("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(stalemate.status(), GameStatus::Stalemate);
+    }
+
+    #[test]
+    fn detects_insufficient_material() {
+        for (fen, drawn) in [
+            ("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1", true),   // king and bishop
+            ("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1", true),   // king and knight
+            ("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1", true),  // bishops on dark squares only
+            ("2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1", false), // bishops on both colors
+            ("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", false),
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(board.status() == GameStatus::InsufficientMaterial, drawn, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn detects_threefold_repetition() {
+        let mut board = Board::new();
+        for (i, m) in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"].iter().enumerate() {
+            assert_eq!(board.status(), GameStatus::Ongoing, "before ply {}", i + 1);
+            board.apply_move(board.parse_uci(m).unwrap());
+        }
+        assert_eq!(board.repetition_count(), 3);
+        assert_eq!(board.status(), GameStatus::ThreefoldRepetition);
+    }
+
+    #[test]
+    fn applies_fifty_move_rule() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 100 80").unwrap();
+        assert_eq!(board.status(), GameStatus::FiftyMoveRule);
+
+        // A mate on the hundredth ply still counts
+        let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80").unwrap();
+        board.apply_move(board.parse_uci("a1a8").unwrap());
+        assert_eq!(board.halfmove_clock, 100);
+        assert_eq!(board.status(), GameStatus::Checkmate(Color::White));
+    }
+}
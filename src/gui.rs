@@ -0,0 +1,287 @@
+use macroquad::prelude::*;
+use macroquad::texture::DrawTextureParams;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use chess::chessboard::{Board, Move, UndoInfo};
+use chess::pgn::GameRecord;
+use chess::PieceKind;
+use chess::AI::eval::EvalWeights;
+use chess::AI::minimax::{iterative_deepening, mate_in, SearchLimits, SearchResult};
+use chess::AI::tt::TranspositionTable;
+
+// Drawing and mouse input for a `Board`. The board itself only knows the
+// rules; everything about what the player has clicked lives here.
+
+pub struct BoardView {
+    selected_square: Option<(i32, i32)>, // (rank, file) of the selected piece
+    possible_moves: Vec<Move>,
+    pub promotion_state: Option<Move>, // pawn move waiting for a piece choice
+    history: Vec<(Move, UndoInfo)>, // moves played so far, newest last
+    redo_stack: Vec<Move>, // moves taken back, next to redo last
+    pub record: GameRecord, // the game so far, for saving as PGN
+}
+
+impl BoardView {
+
+    pub fn new(board: &Board) -> Self {
+        Self {
+            selected_square: None,
+            possible_moves: Vec::new(),
+            promotion_state: None,
+            history: Vec::new(),
+            redo_stack: Vec::new(),
+            record: GameRecord::new(board),
+        }
+    }
+
+    fn square_size(&self) -> f32 {
+        screen_width() / 8.0
+    }
+
+    pub fn draw_pieces(&self, board: &Board, textures: &std::collections::HashMap<String, macroquad::texture::Texture2D>) {
+        for rank in 0..8 {
+            for file in 0..8 {
+                let Some((color, kind)) = board.piece_at((rank, file)) else { continue };
+                let piece_type = match kind {
+                    PieceKind::Pawn => "pawn",
+                    PieceKind::Rook => "rook",
+                    PieceKind::Knight => "knight",
+                    PieceKind::Bishop => "bishop",
+                    PieceKind::Queen => "queen",
+                    PieceKind::King => "king",
+                };
+                let key = format!("{}-{}", color, piece_type);
+                if let Some(tex) = textures.get(&key) {
+                    let scale = 0.75;
+                    let w = tex.width() * scale;
+                    let h = tex.height() * scale;
+                    let x = (file as f32) * self.square_size() + (self.square_size() - w) / 2.0;
+                    let y = (7 - rank) as f32 * self.square_size() + (self.square_size() - h) / 2.0;
+                    draw_texture_ex(tex, x, y, WHITE, DrawTextureParams {
+                        dest_size: Some(Vec2::new(w, h)),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn draw_squares(&self) {
+        for r in 0..8 {
+            for c in (r%2..8).step_by(2) {
+                let y = (7 - r) as f32 * self.square_size();
+                draw_rectangle((c as f32) * self.square_size(), y, self.square_size(), self.square_size(), BEIGE);
+            }
+        }
+
+        for r in 0..8 {
+            for c in ((r+1)%2..8).step_by(2) {
+                let y = (7 - r) as f32 * self.square_size();
+                draw_rectangle((c as f32) * self.square_size(), y, self.square_size(), self.square_size(), WHITE);
+            }
+        }
+
+
+        // Highlight possible moves
+        for &Move { to: (rank, file), .. } in &self.possible_moves {
+            let y = (7 - rank) as f32 * self.square_size();
+            draw_rectangle(file as f32 * self.square_size(), y, self.square_size(), self.square_size(), YELLOW);
+        }
+        // Highlight selected piece
+        if let Some((rank, file)) = self.selected_square {
+            let y = (7 - rank) as f32 * self.square_size();
+            draw_rectangle(file as f32 * self.square_size(), y, self.square_size(), self.square_size(), BLUE);
+        }
+    }
+
+    pub fn mark_piece(&mut self, board: &mut Board, x: f32, y: f32) {
+        let file = (x / self.square_size()) as i32;
+        let rank = 7 - (y / self.square_size()) as i32; // since rank 0 is bottom
+        if (0..8).contains(&file) && (0..8).contains(&rank) {
+            // If a piece is selected, try to move it
+            if self.selected_square.is_some() {
+                if let Some(&m) = self.possible_moves.iter().find(|m| m.to == (rank, file)) {
+                    // Ask which piece a pawn reaching the last rank becomes before playing it
+                    if m.promotion.is_some() {
+                        self.promotion_state = Some(m);
+                    } else {
+                        self.play(board, m);
+                    }
+                }
+                // Deselect after a move or a click elsewhere
+                self.selected_square = None;
+                self.possible_moves.clear();
+                return;
+            }
+            // No piece selected, try to select one
+            if board.piece_at((rank, file)).is_some_and(|(color, _)| color == board.get_current_turn()) {
+                self.selected_square = Some((rank, file));
+                self.possible_moves = board.get_legal_moves((rank, file));
+            }
+        }
+    }
+
+    pub fn promote_piece(&mut self, board: &mut Board, piece_type: PieceKind) {
+        if let Some(mut m) = self.promotion_state.take() {
+            m.promotion = Some(piece_type);
+            self.play(board, m);
+        }
+    }
+
+    // Plays a new move, which forgets any moves that were taken back
+    pub fn play(&mut self, board: &mut Board, m: Move) {
+        let undo = board.apply_move(m);
+        self.history.push((m, undo));
+        self.record.push(m);
+        self.redo_stack.clear();
+    }
+
+    pub fn undo(&mut self, board: &mut Board) {
+        if let Some((m, undo)) = self.history.pop() {
+            board.unmake_move(&m, &undo);
+            self.record.pop();
+            self.redo_stack.push(m);
+        }
+        self.clear_selection();
+    }
+
+    pub fn redo(&mut self, board: &mut Board) {
+        if let Some(m) = self.redo_stack.pop() {
+            let undo = board.apply_move(m);
+            self.history.push((m, undo));
+            self.record.push(m);
+        }
+        self.clear_selection();
+    }
+
+    fn clear_selection(&mut self) {
+        self.selected_square = None;
+        self.possible_moves.clear();
+        self.promotion_state = None;
+    }
+}
+
+// Steps through a loaded game, one position per ply
+pub struct Replay {
+    pub record: GameRecord,
+    positions: Vec<Board>, // positions[i] is the board after i moves
+    sans: Vec<String>, // the moves in notation, for the caption
+    ply: usize,
+}
+
+impl Replay {
+
+    pub fn new(record: GameRecord) -> Self {
+        let positions = record.positions();
+        let sans = record.moves().iter().zip(&positions).map(|(m, board)| board.move_to_san(m)).collect();
+        Self { record, positions, sans, ply: 0 }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.positions[self.ply]
+    }
+
+    pub fn forward(&mut self) {
+        self.ply = (self.ply + 1).min(self.positions.len() - 1);
+    }
+
+    pub fn back(&mut self) {
+        self.ply = self.ply.saturating_sub(1);
+    }
+
+    pub fn first(&mut self) {
+        self.ply = 0;
+    }
+
+    pub fn last(&mut self) {
+        self.ply = self.positions.len() - 1;
+    }
+
+    // "12... Nf6 (23/67)" for the move that led to the board shown
+    pub fn caption(&self) -> String {
+        if self.ply == 0 {
+            return format!("start (0/{})", self.sans.len());
+        }
+        let before = &self.positions[self.ply - 1];
+        let dots = if before.get_current_turn() == chess::Color::White { "." } else { "..." };
+        format!("{}{} {} ({}/{})", before.fullmove_number, dots, self.sans[self.ply - 1], self.ply, self.sans.len())
+    }
+}
+
+// What an engine search is for, which decides what to do with its answer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchPurpose {
+    AutoPlay, // play the best move
+    Hint,     // print the best move and line
+}
+
+enum SearchUpdate {
+    Iteration(SearchResult),
+    Finished(SearchResult),
+}
+
+// An engine search on its own thread, so the window keeps drawing while it
+// thinks. Every finished iteration is sent back over a channel. Dropping the
+// search stops the thread and throws its answer away.
+pub struct BackgroundSearch {
+    pub purpose: SearchPurpose,
+    pub hash: u64, // the position being searched
+    latest: Option<SearchResult>, // the deepest iteration so far
+    updates: Receiver<SearchUpdate>,
+    stop: Arc<AtomicBool>,
+}
+
+impl BackgroundSearch {
+
+    pub fn start(board: &Board, mut limits: SearchLimits, weights: Arc<EvalWeights>, threads: usize, purpose: SearchPurpose) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        limits.stop = Some(Arc::clone(&stop));
+        let (sender, updates) = mpsc::channel();
+        let root = board.clone();
+        std::thread::spawn(move || {
+            let tt = TranspositionTable::default();
+            let result = iterative_deepening(&root, &limits, &tt, &weights, threads, |iteration| {
+                sender.send(SearchUpdate::Iteration(iteration.clone())).ok();
+            });
+            // Nobody is listening any more if the search was cancelled
+            sender.send(SearchUpdate::Finished(result)).ok();
+        });
+        Self { purpose, hash: board.hash(), latest: None, updates, stop }
+    }
+
+    // Reads what the thread sent since the last frame. Returns the result
+    // once the search is done.
+    pub fn poll(&mut self) -> Option<SearchResult> {
+        for update in self.updates.try_iter() {
+            match update {
+                SearchUpdate::Iteration(result) => self.latest = Some(result),
+                SearchUpdate::Finished(result) => return Some(result),
+            }
+        }
+        None
+    }
+
+    // "Thinking... depth 5, +0.32" for the indicator
+    pub fn status(&self) -> String {
+        match &self.latest {
+            Some(result) => format!("Thinking... depth {}, {}", result.depth, describe_score(result.score)),
+            None => "Thinking...".to_string(),
+        }
+    }
+}
+
+impl Drop for BackgroundSearch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// A score from the side to move's point of view, in pawns or as a mate
+pub fn describe_score(score: i32) -> String {
+    match mate_in(score) {
+        Some(moves) if moves > 0 => format!("mate in {}", moves),
+        Some(moves) => format!("mated in {}", -moves),
+        None => format!("{:+.2}", score as f64 / 100.0),
+    }
+}
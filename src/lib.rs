@@ -0,0 +1,11 @@
+pub mod bitboard;
+pub mod zobrist;
+pub mod chessboard;
+pub mod pieces;
+pub mod notation;
+pub mod pgn;
+mod perft;
+#[allow(non_snake_case)]
+pub mod AI;
+
+pub use pieces::piece::{Color, PieceKind};
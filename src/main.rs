@@ -1,10 +1,12 @@
 use macroquad::prelude::*;
 use std::collections::HashMap;
-mod chessboard;
-use chessboard::Board;
-mod pieces;
-mod AI;
-use AI::minimax::minimax;
+use chess::chessboard::{Board, GameStatus};
+use chess::{Color as Side, PieceKind};
+use chess::AI::eval::EvalWeights;
+use chess::AI::minimax::SearchLimits;
+use std::sync::Arc;
+mod gui;
+use gui::{describe_score, BackgroundSearch, BoardView, Replay, SearchPurpose};
 fn window_conf() -> Conf {
     Conf {
         window_title: "Chess".to_owned(),
@@ -33,7 +35,33 @@ async fn main() {
     textures.insert("black-queen".to_string(), load_texture("assets/images/pieces/black-queen.png").await.unwrap());
     textures.insert("black-king".to_string(), load_texture("assets/images/pieces/black-king.png").await.unwrap());
 
-    let mut board = Board::new();
+    // An optional FEN on the command line sets up the starting position, and
+    // a .pgn file opens its first game for replay. `--eval <file>` gives the
+    // engine evaluation weights from a TOML or JSON file.
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut weights = EvalWeights::default();
+    if let Some(i) = args.iter().position(|a| a == "--eval") {
+        let option: Vec<String> = args.drain(i..(i + 2).min(args.len())).collect();
+        match option.get(1).map(|path| (path, EvalWeights::load(path))) {
+            Some((path, Ok(loaded))) => {
+                println!("Loaded evaluation weights from {}", path);
+                weights = loaded;
+            }
+            Some((path, Err(e))) => println!("Could not load {}: {}", path, e),
+            None => println!("--eval needs a file"),
+        }
+    }
+    let weights = Arc::new(weights);
+    let arg = args.into_iter().next();
+    let mut replay = arg.as_deref().filter(|a| a.ends_with(".pgn")).and_then(load_replay);
+    let mut board = match arg {
+        Some(fen) if !fen.ends_with(".pgn") => Board::from_fen(&fen).unwrap_or_else(|e| {
+            println!("Ignoring FEN: {}", e);
+            Board::new()
+        }),
+        _ => Board::new(),
+    };
+    let mut view = BoardView::new(&board);
     let mut x: f32;
     let mut y: f32;
     let mouse: MouseButton = MouseButton::Left;
@@ -41,204 +69,126 @@ async fn main() {
     let mut auto_play = false; // toggle automatic play
     let mut last_move_time = get_time();
     let move_delay = 0.6f64; // seconds between automatic moves
-    let mut auto_move_history: Vec<((i32, i32), (i32, i32))> = Vec::new();
-    let mut position_history: Vec<String> = Vec::new();
-    // record initial position
-    position_history.push(board.position_key());
+    let mut thinking: Option<BackgroundSearch> = None; // the engine search in progress, if any
 
     loop {
         clear_background(BLACK);        
 
+        // Replay mode: arrow keys step through the loaded game, Escape plays on from the shown position
+        if let Some(r) = &mut replay {
+            if is_key_pressed(KeyCode::Right) { r.forward(); }
+            if is_key_pressed(KeyCode::Left) { r.back(); }
+            if is_key_pressed(KeyCode::Up) || is_key_pressed(KeyCode::Home) { r.first(); }
+            if is_key_pressed(KeyCode::Down) || is_key_pressed(KeyCode::End) { r.last(); }
+            view.draw_squares();
+            view.draw_pieces(r.board(), &textures);
+            let players = format!("{} - {}", r.record.tag("White").unwrap_or("?"), r.record.tag("Black").unwrap_or("?"));
+            draw_text(&players, 10.0, 20.0, 20.0, DARKBLUE);
+            draw_text(r.caption(), 10.0, 40.0, 20.0, DARKBLUE);
+            if is_key_pressed(KeyCode::Escape) {
+                board = r.board().clone();
+                view = BoardView::new(&board);
+                replay = None;
+            }
+            next_frame().await;
+            continue;
+        }
+
         // Toggle autoplay with the `P` key
         if is_key_pressed(KeyCode::P) {
             auto_play = !auto_play;
             println!("Auto play: {}", auto_play);
             last_move_time = get_time();
-            // Reset history when toggling autoplay to avoid stale repetition data
-            auto_move_history.clear();
-            position_history.clear();
-            position_history.push(board.position_key());
+            if !auto_play && thinking.as_ref().is_some_and(|s| s.purpose == SearchPurpose::AutoPlay) {
+                thinking = None;
+            }
         }
 
-        // Automatic play loop: when enabled, pick and apply best move every `move_delay` seconds
-        if auto_play && get_time() - last_move_time > move_delay && board.promotion_state.is_none() && !board.is_game_over() {
-            let color = board.get_current_turn().to_string();
-            let moves = board.get_all_moves(&color);
-            if !moves.is_empty() {
-                let mut best_move = moves[0];
-                // Track whether any candidate was evaluated (not skipped due to repetition)
-                let mut evaluated_any = false;
-                let mut skipped: Vec<((i32, i32), (i32, i32))> = Vec::new();
-                // parity determines which indices in history correspond to this player
-                let parity = if color == "white" { 0 } else { 1 };
+        // Cancel the engine search with Escape. Autoplay would only start
+        // another one, so cancelling its search turns it off.
+        if is_key_pressed(KeyCode::Escape) && let Some(search) = thinking.take() {
+            println!("Search cancelled");
+            if search.purpose == SearchPurpose::AutoPlay {
+                auto_play = false;
+                println!("Auto play: {}", auto_play);
+            }
+        }
 
-                if color == "white" {
-                    let mut best_score = i32::MIN;
-                    for m in moves {
-                        // detect strict alternating repetition ABABAB (three repeats)
-                        if auto_move_history.len() >= 6 {
-                            let len = auto_move_history.len();
-                            let a = auto_move_history[len-6];
-                            let b = auto_move_history[len-5];
-                            let c = auto_move_history[len-4];
-                            let d = auto_move_history[len-3];
-                            let e = auto_move_history[len-2];
-                            let f = auto_move_history[len-1];
-                            if a == c && c == e && b == d && d == f {
-                                // next white move would repeat `a` — avoid it
-                                if m == a {
-                                    skipped.push(m);
-                                    continue;
-                                }
-                            }
-                        }
-                        // Count prior consecutive identical moves by this side
-                        let mut same_count = 0;
-                        for (i, past) in auto_move_history.iter().enumerate().rev() {
-                            if i % 2 == parity {
-                                if *past == m { same_count += 1; } else { break; }
-                            }
-                        }
-                        if same_count >= 2 {
-                            skipped.push(m);
-                            continue;
-                        }
+        // Step back and forward through the game with Ctrl+Z / Ctrl+Y
+        let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        if ctrl && is_key_pressed(KeyCode::Z) {
+            view.undo(&mut board);
+        }
+        if ctrl && is_key_pressed(KeyCode::Y) {
+            view.redo(&mut board);
+        }
 
-                        let mut new_board = board.clone();
-                        new_board.make_move(m);
-                        // skip moves that would create a third occurrence of the same position
-                        let key = new_board.position_key();
-                        let mut count = 0;
-                        for pk in &position_history { if pk == &key { count += 1; } }
-                        if count >= 2 { skipped.push(m); continue; }
-                        if new_board.is_in_check("white") { continue; }
-                        let score = minimax(&mut new_board, 2, false);
-                        evaluated_any = true;
-                        if score > best_score {
-                            best_score = score;
-                            best_move = m;
-                        }
-                    }
-                    if !evaluated_any {
-                        // fallback: try skipped moves
-                        for m in skipped.iter().cloned() {
-                            let mut new_board = board.clone();
-                            new_board.make_move(m);
-                            if new_board.is_in_check("white") { continue; }
-                            best_move = m;
-                            break;
-                        }
-                    }
-                } else {
-                    let mut best_score = i32::MAX;
-                    for m in moves {
-                        // detect strict alternating repetition ABABAB (three repeats)
-                        if auto_move_history.len() >= 6 {
-                            let len = auto_move_history.len();
-                            let a = auto_move_history[len-6];
-                            let b = auto_move_history[len-5];
-                            let c = auto_move_history[len-4];
-                            let d = auto_move_history[len-3];
-                            let e = auto_move_history[len-2];
-                            let f = auto_move_history[len-1];
-                            if a == c && c == e && b == d && d == f {
-                                // next black move would repeat `b` — avoid it
-                                if m == b {
-                                    skipped.push(m);
-                                    continue;
-                                }
-                            }
-                        }
-                        
-                        let mut same_count = 0;
-                        for (i, past) in auto_move_history.iter().enumerate().rev() {
-                            if i % 2 == parity {
-                                if *past == m { same_count += 1; } else { break; }
-                            }
-                        }
-                        if same_count >= 2 {
-                            skipped.push(m);
-                            continue;
-                        }
+        // Save the game so far as PGN with the `S` key
+        if is_key_pressed(KeyCode::S) {
+            let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+            let path = format!("game-{}.pgn", secs);
+            match std::fs::write(&path, view.record.to_pgn()) {
+                Ok(()) => println!("Saved game to {}", path),
+                Err(e) => println!("Could not save {}: {}", path, e),
+            }
+        }
 
-                        let mut new_board = board.clone();
-                        new_board.make_move(m);
-                        let key = new_board.position_key();
-                        let mut count = 0;
-                        for pk in &position_history { if pk == &key { count += 1; } }
-                        if count >= 2 { skipped.push(m); continue; }
-                        if new_board.is_in_check("black") { continue; }
-                        let score = minimax(&mut new_board, 2, true);
-                        evaluated_any = true;
-                        if score < best_score {
-                            best_score = score;
-                            best_move = m;
-                        }
-                    }
-                    if !evaluated_any {
-                        for m in skipped.iter().cloned() {
-                            let mut new_board = board.clone();
-                            new_board.make_move(m);
-                            if new_board.is_in_check("black") { continue; }
-                            best_move = m;
-                            break;
-                        }
-                    }
-                }
-                board.make_move(best_move);
-                auto_move_history.push(best_move);
-                // record resulting position
-                position_history.push(board.position_key());
-                // keep history bounded
-                if auto_move_history.len() > 64 {
-                    auto_move_history.remove(0);
-                }
-                println!("Auto {:?} played", best_move);
-                last_move_time = get_time();
+        // Print the current position with the `F` key
+        if is_key_pressed(KeyCode::F) {
+            println!("FEN: {}", board.to_fen());
+        }
+
+        // Print a perft divide of the current position with the `D` key
+        if is_key_pressed(KeyCode::D) {
+            let mut total = 0;
+            for (m, nodes) in board.divide(3) {
+                println!("{}: {}", m.to_uci(), nodes);
+                total += nodes;
             }
+            println!("Nodes searched: {}", total);
         }
 
-        if is_mouse_button_pressed(mouse_right) {
-            let is_white_turn = board.get_current_turn().to_string() == "white";
-            let color = board.get_current_turn().to_string();
-            let moves = board.get_all_moves(&color);
-            if moves.is_empty() {
-                println!("No legal moves for {}", color);
-            } else {
-                let mut best_move = moves[0];
-                if is_white_turn {
-                    let mut best_score = i32::MIN;
-                    for m in moves {
-                        let mut new_board = board.clone();
-                        new_board.make_move(m);
-                        // skip illegal moves that leave white in check
-                        if new_board.is_in_check("white") {
-                            continue;
-                        }
-                        let score = minimax(&mut new_board, 3, false);
-                        if score > best_score {
-                            best_score = score;
-                            best_move = m;
-                        }
-                    }
-                    println!("Best move for white: {:?} -> score {}", best_move, best_score);
-                } else {
-                    let mut best_score = i32::MAX;
-                    for m in moves {
-                        let mut new_board = board.clone();
-                        new_board.make_move(m);
-                        // skip illegal moves that leave black in check
-                        if new_board.is_in_check("black") {
-                            continue;
-                        }
-                        let score = minimax(&mut new_board, 3, true);
-                        if score < best_score {
-                            best_score = score;
-                            best_move = m;
-                        }
-                    }
-                    println!("Best move for black: {:?} -> score {}", best_move, best_score);
+        // Automatic play loop: when enabled, search for the best move every `move_delay` seconds
+        if auto_play && thinking.is_none() && get_time() - last_move_time > move_delay && view.promotion_state.is_none() && !board.is_game_over() {
+            thinking = Some(BackgroundSearch::start(&board, SearchLimits::depth(3), Arc::clone(&weights), 1, SearchPurpose::AutoPlay));
+        }
+
+        // Right click asks for a hint. It is asked for rarely, so it can have every core.
+        if is_mouse_button_pressed(mouse_right) && thinking.is_some() {
+            println!("The engine is already thinking, ask again once it is done");
+        } else if is_mouse_button_pressed(mouse_right) {
+            let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+            thinking = Some(BackgroundSearch::start(&board, SearchLimits::depth(4), Arc::clone(&weights), threads, SearchPurpose::Hint));
+        }
+
+        // A search of a position that is no longer on the board is of no use
+        if thinking.as_ref().is_some_and(|s| s.hash != board.hash()) {
+            thinking = None;
+        }
+
+        if let Some(result) = thinking.as_mut().and_then(|s| s.poll()) {
+            let purpose = thinking.take().map(|s| s.purpose);
+            let color = board.get_current_turn();
+            match (purpose, result.best_move) {
+                // Leave the board alone while the player is choosing a promotion piece
+                (Some(SearchPurpose::AutoPlay), Some(best_move)) if view.promotion_state.is_none() => {
+                    println!("Auto {} played", board.move_to_san(&best_move));
+                    view.record.set_tag(if color == Side::White { "White" } else { "Black" }, "Chess engine");
+                    view.play(&mut board, best_move);
+                    last_move_time = get_time();
                 }
+                (Some(SearchPurpose::Hint), Some(best_move)) => println!(
+                    "Best move for {}: {} -> score {} (depth {}, {} nodes, {} nps, pv {})",
+                    color,
+                    board.move_to_san(&best_move),
+                    describe_score(result.score),
+                    result.depth,
+                    result.nodes,
+                    result.nodes as u128 * 1000 / result.time.as_millis().max(1),
+                    board.line_to_san(&result.pv)
+                ),
+                (Some(SearchPurpose::Hint), None) => println!("No legal moves for {}", color),
+                _ => {}
             }
         }
 
@@ -247,7 +197,7 @@ async fn main() {
 
         if is_mouse_button_pressed(mouse) {
             // Handle promotion selection
-            if let Some(piece_index) = board.promotion_state {
+            if view.promotion_state.is_some() {
                 let promo_box_width = 80.0;
                 let promo_box_height = 80.0;
                 let promo_x = (screen_width() - promo_box_width * 4.0) / 2.0;
@@ -259,23 +209,23 @@ async fn main() {
                 let knight_rect = Rect::new(promo_x + promo_box_width * 3.0, promo_y, promo_box_width, promo_box_height);
                 
                 if queen_rect.contains(Vec2::new(x, y)) {
-                    board.promote_piece(piece_index, 'Q');
+                    view.promote_piece(&mut board, PieceKind::Queen);
                 } else if rook_rect.contains(Vec2::new(x, y)) {
-                    board.promote_piece(piece_index, 'R');
+                    view.promote_piece(&mut board, PieceKind::Rook);
                 } else if bishop_rect.contains(Vec2::new(x, y)) {
-                    board.promote_piece(piece_index, 'B');
+                    view.promote_piece(&mut board, PieceKind::Bishop);
                 } else if knight_rect.contains(Vec2::new(x, y)) {
-                    board.promote_piece(piece_index, 'N');
+                    view.promote_piece(&mut board, PieceKind::Knight);
                 }
             } else {
-                board.mark_piece(x, y);
+                view.mark_piece(&mut board, x, y);
             }
         }
-        board.draw_squares();
-        board.draw_pieces(&textures);
+        view.draw_squares();
+        view.draw_pieces(&board, &textures);
         
         // Draw promotion UI
-        if board.promotion_state.is_some() {
+        if view.promotion_state.is_some() {
             let promo_box_width = 80.0;
             let promo_box_height = 80.0;
             let promo_x = (screen_width() - promo_box_width * 4.0) / 2.0;
@@ -301,15 +251,51 @@ async fn main() {
         }            
 
         // Draw current turn
-        let turn_color = if board.get_current_turn() == "white" { GREEN } else { RED };
-        draw_text(&format!("{}'s turn", board.get_current_turn()), 10.0, 20.0, 20.0, turn_color);
+        let turn_color = if board.get_current_turn() == Side::White { GREEN } else { RED };
+        draw_text(format!("{}'s turn", board.get_current_turn()), 10.0, 20.0, 20.0, turn_color);
 
-        // Check for check
-        if board.is_in_check(board.get_current_turn()) {
+        // Show the result once the game is over, otherwise warn about check
+        let status = board.status();
+        if status != GameStatus::Ongoing {
+            let text = status.to_string();
+            let size = measure_text(&text, None, 40, 1.0);
+            draw_rectangle(0.0, screen_height() / 2.0 - 40.0, screen_width(), 60.0, Color::new(0.0, 0.0, 0.0, 0.7));
+            draw_text(&text, (screen_width() - size.width) / 2.0, screen_height() / 2.0, 40.0, YELLOW);
+        } else if board.is_in_check(board.get_current_turn()) {
             draw_text("Check!", 10.0, 40.0, 20.0, YELLOW);
         }
 
+        // Show that the engine is thinking, and how far it has got
+        if let Some(search) = &thinking {
+            let text = format!("{} (Esc to cancel)", search.status());
+            draw_rectangle(0.0, screen_height() - 30.0, screen_width(), 30.0, Color::new(0.0, 0.0, 0.0, 0.6));
+            draw_text(&text, 10.0, screen_height() - 10.0, 20.0, YELLOW);
+        }
+
         next_frame().await
     }
 }
 
+fn load_replay(path: &str) -> Option<Replay> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            println!("Could not read {}: {}", path, e);
+            return None;
+        }
+    };
+    match chess::pgn::parse_pgn(&text) {
+        Ok(games) if !games.is_empty() => {
+            println!("Replaying game 1 of {} from {}", games.len(), path);
+            games.into_iter().next().map(Replay::new)
+        }
+        Ok(_) => {
+            println!("No games in {}", path);
+            None
+        }
+        Err(e) => {
+            println!("Could not load {}: {}", path, e);
+            None
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use crate::chessboard::{Board, Move, MoveFlag};
+use crate::pieces::piece::PieceKind;
+
+// Standard algebraic notation (Nf3, exd5, O-O, e8=Q+) and the long
+// algebraic form UCI uses (g1f3, e7e8q). SAN depends on the position, so it
+// is written and read through the board the move is played on.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    Invalid(String), // not a move in the notation at all
+    Illegal(String), // well formed, but no legal move matches
+    Ambiguous(String), // more than one legal move matches
+}
+
+impl std::fmt::Display for NotationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotationError::Invalid(s) => write!(f, "'{}' is not a move", s),
+            NotationError::Illegal(s) => write!(f, "'{}' is not legal here", s),
+            NotationError::Ambiguous(s) => write!(f, "'{}' could be more than one move", s),
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+// "e4" for (3, 4)
+pub fn square_name((rank, file): (i32, i32)) -> String {
+    format!("{}{}", (b'a' + file as u8) as char, rank + 1)
+}
+
+pub fn parse_square(text: &str) -> Option<(i32, i32)> {
+    let bytes = text.as_bytes();
+    if bytes.len() != 2 {
+        return None;
+    }
+    let file = bytes[0].wrapping_sub(b'a') as i32;
+    let rank = bytes[1].wrapping_sub(b'1') as i32;
+    if (0..8).contains(&file) && (0..8).contains(&rank) {
+        Some((rank, file))
+    } else {
+        None
+    }
+}
+
+impl Move {
+    pub fn to_uci(&self) -> String {
+        let mut text = format!("{}{}", square_name(self.from), square_name(self.to));
+        if let Some(promotion) = self.promotion {
+            text.push(promotion.letter().to_ascii_lowercase());
+        }
+        text
+    }
+}
+
+impl Board {
+    // `m` must be legal in this position
+    pub fn move_to_san(&self, m: &Move) -> String {
+        let mut san = if m.flag == MoveFlag::Castle {
+            if m.to.1 > m.from.1 { "O-O".to_string() } else { "O-O-O".to_string() }
+        } else {
+            let mut san = String::new();
+            if m.piece == PieceKind::Pawn {
+                if m.is_capture() {
+                    san.push_str(&square_name(m.from)[..1]);
+                }
+            } else {
+                san.push(m.piece.letter());
+                san.push_str(&self.disambiguation(m));
+            }
+            if m.is_capture() {
+                san.push('x');
+            }
+            san.push_str(&square_name(m.to));
+            if let Some(promotion) = m.promotion {
+                san.push('=');
+                san.push(promotion.letter());
+            }
+            san
+        };
+
+        let mut after = self.clone();
+        after.apply_move(*m);
+        if after.is_in_check(after.get_current_turn()) {
+            san.push(if after.get_all_legal_moves().is_empty() { '#' } else { '+' });
+        }
+        san
+    }
+
+    // Just enough of the origin square to tell `m` apart from other pieces of
+    // the same kind that could also reach `m.to`: the file if that does it,
+    // else the rank, else both
+    fn disambiguation(&self, m: &Move) -> String {
+        let rivals: Vec<Move> = self
+            .get_all_legal_moves()
+            .into_iter()
+            .filter(|other| other.piece == m.piece && other.to == m.to && other.from != m.from)
+            .collect();
+        let from = square_name(m.from);
+        if rivals.is_empty() {
+            String::new()
+        } else if rivals.iter().all(|other| other.from.1 != m.from.1) {
+            from[..1].to_string()
+        } else if rivals.iter().all(|other| other.from.0 != m.from.0) {
+            from[1..].to_string()
+        } else {
+            from
+        }
+    }
+
+    // A line of moves starting here in SAN, e.g. a principal variation: "Nf3 d5 g3"
+    pub fn line_to_san(&self, line: &[Move]) -> String {
+        let mut board = self.clone();
+        let mut sans = Vec::new();
+        for m in line {
+            sans.push(board.move_to_san(m));
+            board.apply_move(*m);
+        }
+        sans.join(" ")
+    }
+
+    // Reads SAN leniently: check and annotation marks are ignored, the
+    // capture sign and `=` are optional, and extra disambiguation such as
+    // Ng1f3 or e2-e4 is accepted
+    pub fn parse_san(&self, text: &str) -> Result<Move, NotationError> {
+        let invalid = || NotationError::Invalid(text.to_string());
+        let san = text.trim().trim_end_matches(['+', '#', '!', '?']);
+
+        if matches!(san, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+            let file = if san.len() == 3 { 6 } else { 2 };
+            return self
+                .get_all_legal_moves()
+                .into_iter()
+                .find(|m| m.flag == MoveFlag::Castle && m.to.1 == file)
+                .ok_or_else(|| NotationError::Illegal(text.to_string()));
+        }
+
+        let mut rest = san;
+        let mut promotion = None;
+        if let Some(last) = rest.chars().last().filter(|c| "QRBN".contains(*c)) {
+            promotion = PieceKind::from_letter(last);
+            rest = rest[..rest.len() - 1].trim_end_matches('=');
+        }
+        if rest.len() < 2 || !rest.is_char_boundary(rest.len() - 2) {
+            return Err(invalid());
+        }
+        let to = parse_square(&rest[rest.len() - 2..]).ok_or_else(invalid)?;
+        rest = &rest[..rest.len() - 2];
+
+        let piece = match rest.chars().next() {
+            Some(c) if c.is_ascii_uppercase() => {
+                rest = &rest[1..];
+                PieceKind::from_letter(c).ok_or_else(invalid)?
+            }
+            _ => PieceKind::Pawn,
+        };
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in rest.chars() {
+            match c {
+                'a'..='h' => from_file = Some(c as i32 - 'a' as i32),
+                '1'..='8' => from_rank = Some(c as i32 - '1' as i32),
+                'x' | '-' | ':' => {}
+                _ => return Err(invalid()),
+            }
+        }
+
+        let candidates: Vec<Move> = self
+            .get_all_legal_moves()
+            .into_iter()
+            .filter(|m| {
+                m.piece == piece
+                    && m.to == to
+                    && m.promotion == promotion
+                    && from_file.is_none_or(|f| m.from.1 == f)
+                    && from_rank.is_none_or(|r| m.from.0 == r)
+            })
+            .collect();
+        match candidates.as_slice() {
+            [m] => Ok(*m),
+            [] => Err(NotationError::Illegal(text.to_string())),
+            _ => Err(NotationError::Ambiguous(text.to_string())),
+        }
+    }
+
+    // Long algebraic notation as UCI sends it: e2e4, e1g1 for castling, e7e8q
+    pub fn parse_uci(&self, text: &str) -> Result<Move, NotationError> {
+        let invalid = || NotationError::Invalid(text.to_string());
+        if !(4..=5).contains(&text.len()) || !text.is_ascii() {
+            return Err(invalid());
+        }
+        let from = parse_square(&text[0..2]).ok_or_else(invalid)?;
+        let to = parse_square(&text[2..4]).ok_or_else(invalid)?;
+        let promotion = match text[4..].chars().next() {
+            Some(c) if "qrbn".contains(c) => PieceKind::from_letter(c),
+            Some(_) => return Err(invalid()),
+            None => None,
+        };
+        self.get_all_legal_moves()
+            .into_iter()
+            .find(|m| m.from == from && m.to == to && m.promotion == promotion)
+            .ok_or_else(|| NotationError::Illegal(text.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chessboard::Board;
+
+    fn san_after(fen: &str, uci: &str) -> String {
+        let board = Board::from_fen(fen).unwrap();
+        board.move_to_san(&board.parse_uci(uci).unwrap())
+    }
+
+    #[test]
+    fn formats_san() {
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(san_after(kiwipete, "e1g1"), "O-O");
+        assert_eq!(san_after(kiwipete, "e1c1"), "O-O-O");
+        assert_eq!(san_after(kiwipete, "d5e6"), "dxe6");
+        assert_eq!(san_after(kiwipete, "e5f7"), "Nxf7");
+        assert_eq!(san_after(kiwipete, "f3f6"), "Qxf6");
+        assert_eq!(san_after("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", "d7c8n"), "dxc8=N");
+        assert_eq!(san_after("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2", "d8h4"), "Qh4#");
+        // Knights on b1 and f3 both reach d2, rooks on a1 and a5 both reach a3
+        assert_eq!(san_after("4k3/8/8/R7/8/5N2/8/RN2K3 w - - 0 1", "b1d2"), "Nbd2");
+        assert_eq!(san_after("4k3/8/8/R7/8/5N2/8/RN2K3 w - - 0 1", "a5a3"), "R5a3");
+    }
+
+    #[test]
+    fn san_and_uci_round_trip() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            for m in board.get_all_legal_moves() {
+                assert_eq!(board.parse_san(&board.move_to_san(&m)), Ok(m));
+                assert_eq!(board.parse_uci(&m.to_uci()), Ok(m));
+            }
+        }
+    }
+
+    #[test]
+    fn parses_loose_san() {
+        let board = Board::new();
+        assert_eq!(board.parse_san("Ng1f3").unwrap().to_uci(), "g1f3");
+        assert_eq!(board.parse_san("e2-e4").unwrap().to_uci(), "e2e4");
+        assert_eq!(board.parse_san("Nf3!?").unwrap().to_uci(), "g1f3");
+        assert!(board.parse_san("Ke2").is_err());
+        assert!(board.parse_san("hello").is_err());
+    }
+}
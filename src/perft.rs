@@ -0,0 +1,113 @@
+use crate::chessboard::{Board, Move};
+
+// Perft counts the leaf nodes of the legal move tree. Comparing the totals
+// against published reference numbers is the standard way to find move
+// generation bugs; `divide` splits the total per root move to narrow them down.
+
+impl Board {
+    pub fn perft(&self, depth: u32) -> u64 {
+        self.clone().perft_nodes(depth)
+    }
+
+    pub fn divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        let mut counts = Vec::new();
+        if depth == 0 {
+            return counts;
+        }
+        let mut board = self.clone();
+        for m in self.get_all_legal_moves() {
+            let undo = board.apply_move(m);
+            counts.push((m, board.perft_nodes(depth - 1)));
+            board.unmake_move(&m, &undo);
+        }
+        counts
+    }
+
+    // Walks the tree with apply_move/unmake_move, so every count also checks
+    // that unmake_move restores the position exactly
+    fn perft_nodes(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.get_all_legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut nodes = 0;
+        for m in moves {
+            let undo = self.apply_move(m);
+            nodes += self.perft_nodes(depth - 1);
+            self.unmake_move(&m, &undo);
+        }
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chessboard::Board;
+
+    // Reference positions and node counts from https://www.chessprogramming.org/Perft_Results
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    fn check(fen: &str, expected: &[u64]) {
+        let board = Board::from_fen(fen).unwrap();
+        for (i, &nodes) in expected.iter().enumerate() {
+            let depth = i as u32 + 1;
+            assert_eq!(board.perft(depth), nodes, "perft({}) of {}", depth, fen);
+        }
+    }
+
+    #[test]
+    fn start_position() {
+        check("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn kiwipete() {
+        check(KIWIPETE, &[48, 2039, 97862]);
+    }
+
+    #[test]
+    fn position_3() {
+        check(POSITION_3, &[14, 191, 2812, 43238, 674624]);
+    }
+
+    #[test]
+    fn position_4() {
+        check(POSITION_4, &[6, 264, 9467, 422333]);
+    }
+
+    #[test]
+    fn position_5() {
+        check(POSITION_5, &[44, 1486, 62379]);
+    }
+
+    #[test]
+    fn position_6() {
+        check(POSITION_6, &[46, 2079, 89890]);
+    }
+
+    #[test]
+    fn divide_sums_to_perft() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let total: u64 = board.divide(2).iter().map(|(_, n)| n).sum();
+        assert_eq!(total, board.perft(2));
+    }
+
+    #[test]
+    fn unmake_restores_position() {
+        for fen in [KIWIPETE, POSITION_3, POSITION_4, POSITION_5] {
+            let mut board = Board::from_fen(fen).unwrap();
+            for m in board.get_all_legal_moves() {
+                let undo = board.apply_move(m);
+                board.unmake_move(&m, &undo);
+                assert_eq!(board.to_fen(), fen, "after {:?}", m);
+            }
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chessboard::{Board, FenError, GameStatus, Move, START_FEN};
+use crate::notation::NotationError;
+use crate::pieces::piece::Color;
+
+// A game as PGN stores it: tags, the position it started from and the moves
+// played since. The Seven Tag Roster always comes first and in its fixed order.
+
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+#[derive(Clone)]
+pub struct GameRecord {
+    tags: Vec<(String, String)>,
+    start: Board,
+    moves: Vec<Move>,
+}
+
+impl GameRecord {
+    pub fn new(start: &Board) -> Self {
+        let mut record = GameRecord { tags: Vec::new(), start: start.clone(), moves: Vec::new() };
+        record.set_tag("Event", "Casual game");
+        record.set_tag("Site", "?");
+        record.set_tag("Date", &today());
+        record.set_tag("Round", "-");
+        record.set_tag("White", "?");
+        record.set_tag("Black", "?");
+        record.set_tag("Result", "*");
+        // Games not starting from the usual position carry it along
+        let fen = start.to_fen();
+        if fen != START_FEN {
+            record.set_tag("SetUp", "1");
+            record.set_tag("FEN", &fen);
+        }
+        record
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn start(&self) -> &Board {
+        &self.start
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    // `m` must be legal after the moves recorded so far
+    pub fn push(&mut self, m: Move) {
+        self.moves.push(m);
+    }
+
+    pub fn pop(&mut self) -> Option<Move> {
+        self.moves.pop()
+    }
+
+    // The position before the first move and after each move in turn
+    pub fn positions(&self) -> Vec<Board> {
+        let mut board = self.start.clone();
+        let mut positions = vec![board.clone()];
+        for &m in &self.moves {
+            board.apply_move(m);
+            positions.push(board.clone());
+        }
+        positions
+    }
+
+    pub fn final_position(&self) -> Board {
+        let mut board = self.start.clone();
+        for &m in &self.moves {
+            board.apply_move(m);
+        }
+        board
+    }
+
+    // Ends the game early, e.g. on resignation; otherwise the result follows from the final position
+    pub fn set_result(&mut self, result: &str) {
+        self.set_tag("Result", result);
+    }
+
+    pub fn result(&self) -> String {
+        match result_of(self.final_position().status()) {
+            "*" => self.tag("Result").unwrap_or("*").to_string(),
+            decided => decided.to_string(),
+        }
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let result = self.result();
+        let mut pgn = String::new();
+        for name in SEVEN_TAG_ROSTER {
+            let value = if name == "Result" { result.as_str() } else { self.tag(name).unwrap_or("?") };
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                pgn.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+            }
+        }
+        pgn.push('\n');
+
+        // Movetext: "1. e4 e5 2. Nf3", or "1... e5" when black moves first
+        let mut tokens = Vec::new();
+        let mut board = self.start.clone();
+        for (i, &m) in self.moves.iter().enumerate() {
+            if board.get_current_turn() == Color::White {
+                tokens.push(format!("{}.", board.fullmove_number));
+            } else if i == 0 {
+                tokens.push(format!("{}...", board.fullmove_number));
+            }
+            tokens.push(board.move_to_san(&m));
+            board.apply_move(m);
+        }
+        tokens.push(result);
+
+        // Export format keeps lines under 80 characters
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > 79 {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PgnError {
+    InvalidTag(String),
+    InvalidFen(FenError),
+    InvalidMove { ply: usize, error: NotationError }, // ply counts from 1 within the game
+    Unterminated(&'static str), // a comment, variation or tag left open at the end
+    Unbalanced(char), // a closing bracket with nothing open
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PgnError::InvalidTag(s) => write!(f, "invalid tag pair '{}'", s),
+            PgnError::InvalidFen(e) => write!(f, "invalid FEN tag: {}", e),
+            PgnError::InvalidMove { ply, error } => write!(f, "ply {}: {}", ply, error),
+            PgnError::Unterminated(what) => write!(f, "unterminated {}", what),
+            PgnError::Unbalanced(ch) => write!(f, "unbalanced '{}'", ch),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+// Reads every game in a PGN file. Comments, NAGs, move numbers and
+// annotation marks are dropped, and variations are skipped, so only the
+// main line ends up in each record.
+pub fn parse_pgn(text: &str) -> Result<Vec<GameRecord>, PgnError> {
+    let mut games = Vec::new();
+    let mut tags: Vec<(String, String)> = Vec::new();
+    let mut sans: Vec<String> = Vec::new();
+
+    // Lines starting with % are escaped out entirely
+    let text: String = text
+        .lines()
+        .filter(|line| !line.starts_with('%'))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '[' => {
+                // Tags after movetext belong to the next game
+                if !sans.is_empty() {
+                    games.push(build_game(std::mem::take(&mut tags), &sans, None)?);
+                    sans.clear();
+                }
+                chars.next();
+                let mut pair = String::new();
+                let mut in_string = false;
+                loop {
+                    match chars.next() {
+                        Some('\\') if in_string => pair.extend(chars.next()),
+                        Some('"') => {
+                            in_string = !in_string;
+                            pair.push('"');
+                        }
+                        Some(']') if !in_string => break,
+                        Some(ch) => pair.push(ch),
+                        None => return Err(PgnError::Unterminated("tag pair")),
+                    }
+                }
+                tags.push(parse_tag(&pair)?);
+            }
+            '{' => {
+                chars.next();
+                if !chars.by_ref().any(|ch| ch == '}') {
+                    return Err(PgnError::Unterminated("comment"));
+                }
+            }
+            ';' => {
+                chars.by_ref().find(|&ch| ch == '\n');
+            }
+            '(' => {
+                skip_variation(&mut chars)?;
+            }
+            ')' | ']' | '}' => return Err(PgnError::Unbalanced(c)),
+            _ if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => {
+                let mut token = String::new();
+                while let Some(&ch) = chars.peek() {
+                    if ch.is_whitespace() || "[]{}();".contains(ch) {
+                        break;
+                    }
+                    token.push(ch);
+                    chars.next();
+                }
+                match token.as_str() {
+                    "1-0" | "0-1" | "1/2-1/2" | "*" => {
+                        games.push(build_game(std::mem::take(&mut tags), &sans, Some(&token))?);
+                        sans.clear();
+                    }
+                    _ if token.starts_with('$') => {}
+                    _ => {
+                        // "12." and "12...", possibly glued to the move as in "12...e5".
+                        // Digits without dots are part of the move, as in castling written "0-0".
+                        let number = token.trim_start_matches(|ch: char| ch.is_ascii_digit());
+                        let after_dots = number.trim_start_matches('.');
+                        let san = if after_dots.len() < number.len() { after_dots } else { token.as_str() };
+                        let san = san.trim_end_matches(['!', '?']);
+                        if !san.is_empty() {
+                            sans.push(san.to_string());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if !sans.is_empty() || !tags.is_empty() {
+        games.push(build_game(tags, &sans, None)?);
+    }
+    Ok(games)
+}
+
+// Skips a parenthesised variation, including nested ones and any comments
+// inside, which may themselves contain parentheses
+fn skip_variation(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<(), PgnError> {
+    let mut depth = 0;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(());
+                }
+            }
+            '{' if !chars.by_ref().any(|c| c == '}') => return Err(PgnError::Unterminated("comment")),
+            ';' => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            _ => {}
+        }
+    }
+    Err(PgnError::Unterminated("variation"))
+}
+
+// `Name "value"` with the brackets already removed
+fn parse_tag(pair: &str) -> Result<(String, String), PgnError> {
+    let invalid = || PgnError::InvalidTag(pair.to_string());
+    let (name, value) = pair.trim().split_once(char::is_whitespace).ok_or_else(invalid)?;
+    let value = value.trim().strip_prefix('"').and_then(|v| v.strip_suffix('"')).ok_or_else(invalid)?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(invalid());
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+fn build_game(tags: Vec<(String, String)>, sans: &[String], result: Option<&str>) -> Result<GameRecord, PgnError> {
+    let start = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => Board::from_fen(fen).map_err(PgnError::InvalidFen)?,
+        None => Board::new(),
+    };
+    let mut record = GameRecord { tags, start: start.clone(), moves: Vec::new() };
+    let mut board = start;
+    for (i, san) in sans.iter().enumerate() {
+        let m = board.parse_san(san).map_err(|error| PgnError::InvalidMove { ply: i + 1, error })?;
+        board.apply_move(m);
+        record.push(m);
+    }
+    // The token after the moves wins over a disagreeing Result tag
+    if let Some(result) = result {
+        record.set_result(result);
+    }
+    Ok(record)
+}
+
+// The PGN result token for a game in this state
+pub fn result_of(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::Ongoing => "*",
+        GameStatus::Checkmate(Color::White) => "1-0",
+        GameStatus::Checkmate(Color::Black) => "0-1",
+        GameStatus::Stalemate
+        | GameStatus::FiftyMoveRule
+        | GameStatus::ThreefoldRepetition
+        | GameStatus::FivefoldRepetition
+        | GameStatus::InsufficientMaterial => "1/2-1/2",
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Today's UTC date as PGN writes it, "2024.05.17"
+fn today() -> String {
+    let Ok(elapsed) = SystemTime::now().duration_since(UNIX_EPOCH) else {
+        return "????.??.??".to_string();
+    };
+    // Days since 1970-01-01 to a civil date, Howard Hinnant's civil_from_days
+    let days = (elapsed.as_secs() / 86_400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_pgn, GameRecord, PgnError};
+    use crate::chessboard::Board;
+
+    #[test]
+    fn exports_fools_mate() {
+        let mut board = Board::new();
+        let mut record = GameRecord::new(&board);
+        for san in ["f3", "e5", "g4", "Qh4#"] {
+            let m = board.parse_san(san).unwrap();
+            board.apply_move(m);
+            record.push(m);
+        }
+        record.set_tag("White", "Patzer");
+        let pgn = record.to_pgn();
+        let lines: Vec<&str> = pgn.lines().collect();
+        assert_eq!(lines[0], "[Event \"Casual game\"]");
+        assert_eq!(lines[4], "[White \"Patzer\"]");
+        assert_eq!(lines[6], "[Result \"0-1\"]");
+        assert_eq!(lines[7], "");
+        assert_eq!(lines[8], "1. f3 e5 2. g4 Qh4# 0-1");
+    }
+
+    #[test]
+    fn exports_from_a_set_up_position() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 12").unwrap();
+        let mut record = GameRecord::new(&board);
+        record.push(board.parse_san("Kd7").unwrap());
+        let pgn = record.to_pgn();
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 12\"]\n"));
+        assert!(pgn.ends_with("\n12... Kd7 *\n"));
+    }
+
+    #[test]
+    fn imports_annotated_games() {
+        let pgn = "[Event \"Annotated\"]\n[White \"Morphy, Paul\"]\n[Black \"Duke Karl / Count Isouard\"]\n[Result \"1-0\"]\n\n\
+            1. e4 e5 2. Nf3 d6 {Philidor Defence} 3. d4 Bg4?! $6 4. dxe5 Bxf3 5. Qxf3 dxe5\n\
+            6. Bc4 Nf6 7. Qb3 (7. Qg3!? {also good (really)} Qe7 (7... Nc6)) 7... Qe7 8. Nc3 c6\n\
+            9. Bg5 b5 10. Nxb5 cxb5 11. Bxb5+ Nbd7 12. O-O-O Rd8 13. Rxd7 Rxd7 14. Rd1 Qe6\n\
+            15. Bxd7+ Nxd7 16. Qb8+ Nxb8 17. Rd8# 1-0\n\n\
+            ; a second game\n[Event \"Short\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n\n\
+            1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 Nf6 *\n";
+        let games = parse_pgn(pgn).unwrap();
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].tag("White"), Some("Morphy, Paul"));
+        assert_eq!(games[0].moves().len(), 33);
+        assert_eq!(games[0].result(), "1-0");
+        assert_eq!(games[0].positions().len(), 34);
+        assert_eq!(games[1].moves().len(), 4);
+        assert_eq!(games[2].moves().len(), 8);
+
+        // Exporting and importing again gives the same game
+        let again = parse_pgn(&games[0].to_pgn()).unwrap();
+        assert_eq!(again[0].moves(), games[0].moves());
+    }
+
+    #[test]
+    fn reports_bad_pgn() {
+        assert!(matches!(parse_pgn("1. e4 e5 2. Ke3"), Err(PgnError::InvalidMove { ply: 3, .. })));
+        assert!(matches!(parse_pgn("1. e4 {unfinished"), Err(PgnError::Unterminated("comment"))));
+        assert!(matches!(parse_pgn("[Event]\n1. e4 *"), Err(PgnError::InvalidTag(_))));
+        assert!(matches!(parse_pgn("1. e4 e5 } 2. Nf3 *"), Err(PgnError::Unbalanced('}'))));
+        assert!(matches!(parse_pgn("1. e4 e5 ) 2. Nf3 *"), Err(PgnError::Unbalanced(')'))));
+    }
+}
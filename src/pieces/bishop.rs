@@ -1,94 +1,14 @@
-use crate::pieces::piece::{Piece};
-
-#[derive(Clone)]
-pub struct Bishop {    
-    name: String,
-    rank: i32, 
-    file: i32,
-    pub color: String, 
+use crate::bitboard::{ray_attacks, NORTH_EAST, NORTH_WEST, SOUTH_EAST, SOUTH_WEST};
+use crate::chessboard::Board;
+use crate::pieces::piece::Color;
+
+pub fn attacks(square: usize, occupied: u64) -> u64 {
+    ray_attacks(square, NORTH_EAST, occupied)
+        | ray_attacks(square, NORTH_WEST, occupied)
+        | ray_attacks(square, SOUTH_EAST, occupied)
+        | ray_attacks(square, SOUTH_WEST, occupied)
 }
 
-
-impl Bishop {
-    pub fn new(color: &str, rank: i32, file: i32) -> Self {
-        Self {
-            name: "B".to_string(),
-            rank: rank, 
-            file: file, 
-            color: color.to_string(),
-        }
-    }
+pub fn get_possible_moves(board: &Board, square: usize, color: Color) -> u64 {
+    attacks(square, board.occupied()) & !board.occupancy(color)
 }
-
-
-impl Piece for Bishop {
-
-
-    fn set_color(&mut self, color: String) -> () {
-        self.color = color;
-    }
-
-
-    fn get_color(&self) -> &str {
-        return &self.color
-    }
-
-    
-    fn get_rank(&self) -> i32 {
-        return self.rank;
-    }
-
-    fn get_file(&self) -> i32 {
-        return self.file;
-    }
-
-    fn set_rank(&mut self, rank: i32) {
-        self.rank = rank;
-    }
-
-    fn set_file(&mut self, file: i32) {
-        self.file = file;
-    }
-
-    fn move_piece(&self) -> () {
-
-    }
-
-    fn get_name(&self) -> &str {
-        return &(self.name);
-    }
-
-    fn get_possible_moves(&self, board: &crate::chessboard::Board) -> Vec<(i32, i32)> {
-        let mut moves = Vec::new();
-        let directions = [(1, 1), (1, -1), (-1, 1), (-1, -1)]; // diagonals
-        for &(dr, df) in &directions {
-            let mut r = self.rank + dr;
-            let mut f = self.file + df;
-            while r >= 0 && r < 8 && f >= 0 && f < 8 {
-                let mut occupied = false;
-                let mut capture = false;
-                for p in &board.pieces {
-                    if p.get_rank() == r && p.get_file() == f {
-                        occupied = true;
-                        if p.get_color() != self.color {
-                            capture = true;
-                        }
-                        break;
-                    }
-                }
-                if occupied {
-                    if capture {
-                        moves.push((r, f));
-                    }
-                    break;
-                } else {
-                    moves.push((r, f));
-                }
-                r += dr;
-                f += df;
-            }
-        }
-        moves
-    }
-     
-}
\ No newline at end of file
@@ -7,4 +7,4 @@ pub mod queen;
 
 
 
-pub mod piece; // re-export the piece trait
+pub mod piece; // the Color and PieceKind types
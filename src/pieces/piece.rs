@@ -1,11 +1,128 @@
-pub trait Piece {
-    fn get_name(&self) -> &str;
-    fn move_piece(&self) -> ();
-    fn get_rank(&self) -> i32;
-    fn get_file(&self) -> i32;
-    fn set_rank(&mut self, rank: i32);
-    fn set_file(&mut self, file: i32);
-    fn get_color(&self) -> &str;
-    fn set_color(&mut self, color: String) -> ();
-    fn get_possible_moves(&self, board: &crate::chessboard::Board) -> Vec<(i32, i32)>;
-}
\ No newline at end of file
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Color {
+    White,
+    Black,
+}
+
+impl Color {
+    pub const ALL: [Color; 2] = [Color::White, Color::Black];
+
+    pub fn opponent(self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+
+    // Position in per-color tables such as the board's bitboards
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Color::White => "white",
+            Color::Black => "black",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseColorError(pub String);
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown color '{}', expected white or black", self.0)
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Color, ParseColorError> {
+        match s {
+            "white" => Ok(Color::White),
+            "black" => Ok(Color::Black),
+            _ => Err(ParseColorError(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PieceKind {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+impl PieceKind {
+    pub const ALL: [PieceKind; 6] = [
+        PieceKind::Pawn,
+        PieceKind::Knight,
+        PieceKind::Bishop,
+        PieceKind::Rook,
+        PieceKind::Queen,
+        PieceKind::King,
+    ];
+
+    // Position in per-kind tables such as the board's bitboards
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    // The uppercase letter used in FEN and algebraic notation
+    pub fn letter(self) -> char {
+        match self {
+            PieceKind::Pawn => 'P',
+            PieceKind::Knight => 'N',
+            PieceKind::Bishop => 'B',
+            PieceKind::Rook => 'R',
+            PieceKind::Queen => 'Q',
+            PieceKind::King => 'K',
+        }
+    }
+
+    // Either case, so FEN letters of both colors parse
+    pub fn from_letter(letter: char) -> Option<PieceKind> {
+        PieceKind::ALL.into_iter().find(|kind| kind.letter() == letter.to_ascii_uppercase())
+    }
+}
+
+impl fmt::Display for PieceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.letter())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePieceKindError(pub String);
+
+impl fmt::Display for ParsePieceKindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown piece '{}', expected one of P, N, B, R, Q, K", self.0)
+    }
+}
+
+impl std::error::Error for ParsePieceKindError {}
+
+impl FromStr for PieceKind {
+    type Err = ParsePieceKindError;
+
+    fn from_str(s: &str) -> Result<PieceKind, ParsePieceKindError> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(letter), None) => PieceKind::from_letter(letter).ok_or_else(|| ParsePieceKindError(s.to_string())),
+            _ => Err(ParsePieceKindError(s.to_string())),
+        }
+    }
+}
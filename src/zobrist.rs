@@ -0,0 +1,129 @@
+// Zobrist hashing: every (color, piece, square), the side to move, each
+// castling-rights combination and each en passant file gets a fixed random
+// number, and a position's hash is the XOR of the numbers that apply to it.
+// XOR undoes itself, so moving a piece updates the hash with two XORs
+// instead of rehashing the whole board.
+
+// splitmix64, run at compile time so the keys are the same in every build
+const fn next(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+struct Keys {
+    pieces: [[[u64; 64]; 6]; 2], // [color][kind][square]
+    side: u64,
+    castling: [u64; 16], // indexed by the rights as bits: K = 1, Q = 2, k = 4, q = 8
+    en_passant: [u64; 8], // by file
+}
+
+const fn keys() -> Keys {
+    let mut keys = Keys { pieces: [[[0; 64]; 6]; 2], side: 0, castling: [0; 16], en_passant: [0; 8] };
+    let mut state = 0x2545_F491_4F6C_DD1D;
+    let mut color = 0;
+    while color < 2 {
+        let mut kind = 0;
+        while kind < 6 {
+            let mut sq = 0;
+            while sq < 64 {
+                let (s, key) = next(state);
+                state = s;
+                keys.pieces[color][kind][sq] = key;
+                sq += 1;
+            }
+            kind += 1;
+        }
+        color += 1;
+    }
+    let (s, key) = next(state);
+    state = s;
+    keys.side = key;
+    let mut i = 0;
+    while i < 16 {
+        let (s, key) = next(state);
+        state = s;
+        keys.castling[i] = key;
+        i += 1;
+    }
+    let mut file = 0;
+    while file < 8 {
+        let (s, key) = next(state);
+        state = s;
+        keys.en_passant[file] = key;
+        file += 1;
+    }
+    keys
+}
+
+const KEYS: Keys = keys();
+
+pub fn piece(color: usize, kind: usize, square: usize) -> u64 {
+    KEYS.pieces[color][kind][square]
+}
+
+// Included when black is to move
+pub fn side() -> u64 {
+    KEYS.side
+}
+
+pub fn castling(rights: usize) -> u64 {
+    KEYS.castling[rights]
+}
+
+pub fn en_passant(file: i32) -> u64 {
+    KEYS.en_passant[file as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chessboard::Board;
+
+    // Compares the incrementally kept hash with one computed from scratch
+    // after every move and every unmake of a small tree
+    fn check_tree(board: &mut Board, depth: u32) {
+        assert_eq!(board.hash(), board.compute_hash(), "{}", board.to_fen());
+        if depth == 0 {
+            return;
+        }
+        for m in board.get_all_legal_moves() {
+            let before = board.hash();
+            let undo = board.apply_move(m);
+            check_tree(board, depth - 1);
+            board.unmake_move(&m, &undo);
+            assert_eq!(board.hash(), before);
+        }
+    }
+
+    #[test]
+    fn incremental_hash_matches_full_hash() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ] {
+            check_tree(&mut Board::from_fen(fen).unwrap(), 3);
+        }
+    }
+
+    #[test]
+    fn transpositions_share_a_hash() {
+        let play = |moves: &[&str]| {
+            let mut board = Board::new();
+            for text in moves {
+                let m = board.parse_uci(text).unwrap();
+                board.apply_move(m);
+            }
+            board.hash()
+        };
+        assert_eq!(play(&["g1f3", "g8f6", "b1c3"]), play(&["b1c3", "g8f6", "g1f3"]));
+        assert_ne!(play(&["g1f3", "g8f6"]), play(&["g1f3"]));
+        // Same pieces, but only straight after e2e4 can the d4 pawn take en passant
+        assert_ne!(
+            play(&["g1f3", "d7d5", "f3g1", "d5d4", "e2e4"]),
+            play(&["e2e4", "d7d5", "g1f3", "d5d4", "f3g1"])
+        );
+    }
+}